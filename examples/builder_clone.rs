@@ -0,0 +1,66 @@
+use smart_clone::SmartClone;
+
+#[derive(SmartClone, PartialEq, Debug)]
+#[clone(builder)]
+pub struct Request<T: Clone> {
+    url: String,
+    headers: Vec<(String, String)>,
+    body: T,
+    #[clone(default)]
+    retries: u32,
+}
+
+// Will be expanded to :
+// ```
+// impl<T: Clone> Clone for Request<T> { ... }
+// pub struct RequestCloneBuilder<'source, T: Clone> {
+//     source: &'source Request<T>,
+//     url: Option<String>,
+//     headers: Option<Vec<(String, String)>>,
+//     body: Option<T>,
+//     retries: Option<u32>,
+// }
+// impl<T: Clone> Request<T> {
+//     pub fn clone_builder<'source>(&'source self) -> RequestCloneBuilder<'source, T> { ... }
+// }
+// impl<'source, T: Clone> RequestCloneBuilder<'source, T> {
+//     pub fn url(mut self, value: String) -> Self { ... }
+//     ...
+//     pub fn build(self) -> Request<T> {
+//         Request {
+//             url: match self.url {
+//                 Some(value) => value,
+//                 None => self.source.url.clone(),
+//             },
+//             ...
+//             retries: match self.retries {
+//                 Some(value) => value,
+//                 None => Default::default(),
+//             },
+//         }
+//     }
+// }
+// ```
+
+fn main() {
+    let request = Request {
+        url: String::from("https://example.com"),
+        headers: vec![(String::from("Accept"), String::from("text/html"))],
+        body: vec![0u8; 1024],
+        retries: 3,
+    };
+
+    // The body is replaced rather than cloned.
+    let cloned = request.clone_builder().body(Vec::new()).build();
+    assert_eq!(cloned.url, request.url);
+    assert_eq!(cloned.headers, request.headers);
+    assert!(cloned.body.is_empty());
+    assert_eq!(cloned.retries, 0);
+
+    // The unset fields follow the `#[clone(...)]` options.
+    let cloned = request.clone_builder().retries(5).build();
+    assert_eq!(cloned.body, request.body);
+    assert_eq!(cloned.retries, 5);
+
+    assert_eq!(request.clone_builder().build(), request.clone());
+}
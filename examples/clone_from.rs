@@ -0,0 +1,103 @@
+use smart_clone::SmartClone;
+
+#[derive(SmartClone, PartialEq, Debug)]
+struct Config {
+    name: String,
+    values: Vec<u32>,
+    #[clone(default)]
+    cache: Vec<u32>,
+}
+
+// Will be expanded to :
+// ```
+// impl Clone for Config {
+//     fn clone(&self) -> Self {
+//         Self {
+//             name: self.name.clone(),
+//             values: self.values.clone(),
+//             cache: Default::default(),
+//         }
+//     }
+//     fn clone_from(&mut self, source: &Self) {
+//         self.name.clone_from(&source.name);
+//         self.values.clone_from(&source.values);
+//         self.cache = Default::default();
+//     }
+// }
+// ```
+
+#[derive(SmartClone, PartialEq, Debug)]
+enum State {
+    Idle,
+    Running { job: String, progress: Vec<u8> },
+}
+
+// Will be expanded to :
+// ```
+// impl Clone for State {
+//     fn clone(&self) -> Self {
+//         match self {
+//             State::Idle => State::Idle,
+//             State::Running { job, progress } => State::Running {
+//                 job: job.clone(),
+//                 progress: progress.clone(),
+//             },
+//         }
+//     }
+//     fn clone_from(&mut self, source: &Self) {
+//         match (&mut *self, source) {
+//             (
+//                 State::Running { job: self_job, progress: self_progress },
+//                 State::Running { job, progress },
+//             ) => {
+//                 self_job.clone_from(job);
+//                 self_progress.clone_from(progress);
+//             }
+//             _ => *self = source.clone(),
+//         }
+//     }
+// }
+// ```
+
+fn main() {
+    let source = Config {
+        name: String::from("tick"),
+        values: vec![1, 2, 3],
+        cache: vec![4, 5, 6],
+    };
+    let mut target = Config {
+        name: String::with_capacity(64),
+        values: Vec::with_capacity(64),
+        cache: vec![7],
+    };
+    let values = target.values.as_ptr();
+    target.clone_from(&source);
+    assert_eq!(
+        target,
+        Config {
+            name: String::from("tick"),
+            values: vec![1, 2, 3],
+            cache: vec![],
+        }
+    );
+    // The allocation of `values` has been reused.
+    assert_eq!(target.values.as_ptr(), values);
+
+    let source = State::Running {
+        job: String::from("build"),
+        progress: vec![50],
+    };
+    let mut target = State::Running {
+        job: String::with_capacity(64),
+        progress: Vec::with_capacity(64),
+    };
+    target.clone_from(&source);
+    assert_eq!(target, source);
+    if let State::Running { job, .. } = &target {
+        assert_eq!(job.capacity(), 64);
+    }
+
+    let mut target = State::Idle;
+    target.clone_from(&source);
+    assert_eq!(target, source);
+}
@@ -0,0 +1,97 @@
+use smart_clone::SmartClone;
+
+#[derive(Default)]
+struct IdAllocator {
+    next: u64,
+}
+
+impl IdAllocator {
+    fn next(_previous: &u64, allocator: &mut IdAllocator) -> u64 {
+        allocator.next += 1;
+        allocator.next
+    }
+}
+
+#[derive(SmartClone, PartialEq, Debug)]
+#[clone(context = IdAllocator)]
+struct Node {
+    #[clone(with_ctx = IdAllocator::next)]
+    id: u64,
+    name: String,
+    #[clone(with_ctx = |children: &Vec<Node>, ctx: &mut IdAllocator| children.iter().map(|child| child.clone_with_ctx(ctx)).collect())]
+    children: Vec<Node>,
+}
+
+// Will be expanded to :
+// ```
+// impl Clone for Node {
+//     fn clone(&self) -> Self {
+//         Self {
+//             id: self.id.clone(),
+//             name: self.name.clone(),
+//             children: self.children.clone(),
+//         }
+//     }
+//     fn clone_from(&mut self, source: &Self) { ... }
+// }
+// impl Node {
+//     fn clone_with_ctx(&self, ctx: &mut IdAllocator) -> Self {
+//         Self {
+//             id: IdAllocator::next(&self.id, &mut *ctx),
+//             name: self.name.clone(),
+//             children: (|children: &Vec<Node>, ctx: &mut IdAllocator| ...)(&self.children, &mut *ctx),
+//         }
+//     }
+// }
+// ```
+
+struct Interner {
+    names: Vec<String>,
+}
+
+#[derive(SmartClone, PartialEq, Debug)]
+#[clone(context = Interner, skip_clone)] // `Clone` is not implemented.
+enum Symbol {
+    #[clone(with_ctx = Symbol::intern)]
+    Name(String),
+    Interned(usize),
+}
+
+impl Symbol {
+    fn intern(&self, interner: &mut Interner) -> Symbol {
+        match self {
+            Symbol::Name(name) => {
+                interner.names.push(name.clone());
+                Symbol::Interned(interner.names.len() - 1)
+            }
+            Symbol::Interned(index) => Symbol::Interned(*index),
+        }
+    }
+}
+
+fn main() {
+    let tree = Node {
+        id: 0,
+        name: String::from("root"),
+        children: vec![Node {
+            id: 0,
+            name: String::from("leaf"),
+            children: vec![],
+        }],
+    };
+
+    // `Clone` is still generated and clones as usual.
+    assert_eq!(tree.clone(), tree);
+
+    // `clone_with_ctx` allocates new ids.
+    let mut allocator = IdAllocator::default();
+    let cloned = tree.clone_with_ctx(&mut allocator);
+    assert_eq!(cloned.id, 1);
+    assert_eq!(cloned.children[0].id, 2);
+    assert_eq!(cloned.children[0].name, "leaf");
+
+    let mut interner = Interner { names: vec![] };
+    let symbol = Symbol::Name(String::from("main"));
+    assert_eq!(symbol.clone_with_ctx(&mut interner), Symbol::Interned(0));
+    assert_eq!(interner.names, vec![String::from("main")]);
+}
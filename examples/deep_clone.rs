@@ -0,0 +1,66 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::sync::{Arc, RwLock};
+
+use smart_clone::{DeepClone, SmartClone};
+
+#[derive(SmartClone, DeepClone, Debug)]
+struct Config {
+    values: Arc<RwLock<Vec<u32>>>,
+}
+
+#[derive(SmartClone, Debug)]
+struct Document {
+    name: String,
+    #[clone(deep)]
+    config: Arc<Config>,
+    shared: Arc<RwLock<u32>>,
+}
+
+// Will be expanded to :
+// ```
+// impl Clone for Document {
+//     fn clone(&self) -> Self {
+//         Self {
+//             name: self.name.clone(),
+//             config: <Arc<Config>>::new(/* `Config::deep_clone(&*self.config)` */),
+//             shared: self.shared.clone(),
+//         }
+//     }
+//     fn clone_from(&mut self, source: &Self) { ... }
+// }
+// ```
+
+#[derive(DeepClone, Debug)]
+enum Tree<T> {
+    Leaf(T),
+    Node(Vec<Rc<RefCell<Tree<T>>>>),
+}
+
+fn main() {
+    let document = Document {
+        name: String::from("draft"),
+        config: Arc::new(Config {
+            values: Arc::new(RwLock::new(vec![1, 2])),
+        }),
+        shared: Arc::new(RwLock::new(0)),
+    };
+
+    // The config is unshared, down to its values, while the other fields are cloned as usual.
+    let copy = document.clone();
+    copy.config.values.write().unwrap().push(3);
+    *copy.shared.write().unwrap() = 1;
+    assert_eq!(*document.config.values.read().unwrap(), vec![1, 2]);
+    assert_eq!(*document.shared.read().unwrap(), 1);
+
+    // A whole tree is unshared in one call.
+    let leaf = Rc::new(RefCell::new(Tree::Leaf(1)));
+    let tree = Tree::Node(vec![leaf.clone(), leaf.clone()]);
+    let copy = tree.deep_clone();
+    let Tree::Node(children) = &copy else {
+        unreachable!()
+    };
+    *children[0].borrow_mut() = Tree::Leaf(2);
+    assert!(matches!(*leaf.borrow(), Tree::Leaf(1)));
+    assert!(matches!(*children[1].borrow(), Tree::Leaf(1)));
+}
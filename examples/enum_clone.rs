@@ -1,5 +1,3 @@
-#![allow(unused_variables)]
-
 use smart_clone::SmartClone;
 
 #[derive(SmartClone, PartialEq, Debug, Default)]
@@ -11,14 +9,14 @@ enum SimpleEnum {
         x: u8,
         y: u8,
     },
-    #[clone(SimpleEnum::D(8, 12))]
+    #[clone(value = SimpleEnum::D(*v0 + 8, 12))] // The fields are bound as `v0, v1...`
     D(i32, u32),
-    #[clone(SimpleEnum::E { x: 3, y: 4 })]
+    #[clone(value = SimpleEnum::E { x: 3, y: 4 })]
     E {
         x: u8,
         y: u8,
     },
-    #[clone(clone_with = "double")]
+    #[clone(with_fields = double)]
     F {
         x: u8,
         y: u8,
@@ -28,6 +26,17 @@ enum SimpleEnum {
         x: u8,
         y: u8,
     },
+    H {
+        x: u8,
+        #[clone(default)]
+        cache: Vec<u8>,
+    },
+    #[clone(reset)]
+    I {
+        x: u8,
+        #[clone(value = 1)]
+        y: u8,
+    },
 }
 
 // Will be expanded to :
@@ -41,20 +50,25 @@ enum SimpleEnum {
 //                 x: x.clone(),
 //                 y: y.clone(),
 //             },
-//             SimpleEnum::D(..) => SimpleEnum::D(8, 12),
+//             SimpleEnum::D(v0, v1) => SimpleEnum::D(*v0 + 8, 12),
 //             SimpleEnum::E { x, y } => SimpleEnum::E { x: 3, y: 4 },
-//             SimpleEnum::F { x, y } => double(self),
-//             SimpleEnum::G { x, y } => Default::default(),
+//             SimpleEnum::F { x, y } => double(x, y),
+//             SimpleEnum::G { .. } => Default::default(),
+//             SimpleEnum::H { x, .. } => SimpleEnum::H {
+//                 x: x.clone(),
+//                 cache: Default::default(),
+//             },
+//             SimpleEnum::I { .. } => SimpleEnum::I {
+//                 x: <u8 as Default>::default(),
+//                 y: 1,
+//             },
 //         }
 //     }
 // }
 // ```
 
-fn double(input: &SimpleEnum) -> SimpleEnum {
-    match input {
-        SimpleEnum::F { x, y } => SimpleEnum::F { x: x * 2, y: y * 2 },
-        _ => unimplemented!(),
-    }
+fn double(x: &u8, y: &u8) -> SimpleEnum {
+    SimpleEnum::F { x: x * 2, y: y * 2 }
 }
 
 fn main() {
@@ -68,7 +82,7 @@ fn main() {
     assert_eq!(enum_c.clone(), SimpleEnum::C { x: 1, y: 2 });
 
     let enum_d = SimpleEnum::D(20, 30);
-    assert_eq!(enum_d.clone(), SimpleEnum::D(8, 12));
+    assert_eq!(enum_d.clone(), SimpleEnum::D(28, 12));
 
     let enum_e = SimpleEnum::E { x: 1, y: 2 };
     assert_eq!(enum_e.clone(), SimpleEnum::E { x: 3, y: 4 });
@@ -78,4 +92,19 @@ fn main() {
 
     let enum_g = SimpleEnum::G { x: 7, y: 8 };
     assert_eq!(enum_g.clone(), SimpleEnum::A);
+
+    let enum_h = SimpleEnum::H {
+        x: 9,
+        cache: vec![1, 2, 3],
+    };
+    assert_eq!(
+        enum_h.clone(),
+        SimpleEnum::H {
+            x: 9,
+            cache: vec![]
+        }
+    );
+
+    let enum_i = SimpleEnum::I { x: 7, y: 8 };
+    assert_eq!(enum_i.clone(), SimpleEnum::I { x: 0, y: 1 });
 }
@@ -0,0 +1,96 @@
+use std::marker::PhantomData;
+use std::sync::Arc;
+
+use smart_clone::SmartClone;
+
+// Not `Clone`: only used in fields that are not cloned as usual.
+#[derive(PartialEq, Debug, Default)]
+struct Handle(u32);
+
+#[derive(SmartClone, PartialEq, Debug)]
+struct Cache<K, V, M> {
+    keys: Vec<K>, // left for standard clone: `K: Clone` is required
+    #[clone(default)]
+    values: Vec<V>, // reset on clone: no `V: Clone` required
+    marker: PhantomData<M>, // `PhantomData<M>` is always `Clone`: no `M: Clone` required
+}
+
+// Will be expanded to :
+// ```
+// impl<K, V, M> Clone for Cache<K, V, M>
+// where
+//     K: Clone,
+// {
+//     fn clone(&self) -> Self {
+//         Self {
+//             keys: self.keys.clone(),
+//             values: Default::default(),
+//             marker: self.marker.clone(),
+//         }
+//     }
+// }
+// ```
+
+#[derive(SmartClone, PartialEq, Debug)]
+struct Shared<T, U> {
+    #[clone(bound = "")]
+    config: Arc<T>, // `Arc<T>` is always `Clone`: replace the inferred `T: Clone` by nothing
+    #[clone(default, bound = "U: Default")]
+    scratch: U, // reset on clone: `U: Default` is required instead
+}
+
+// Will be expanded to :
+// ```
+// impl<T, U> Clone for Shared<T, U>
+// where
+//     U: Default,
+// {
+//     fn clone(&self) -> Self {
+//         Self {
+//             config: self.config.clone(),
+//             scratch: Default::default(),
+//         }
+//     }
+// }
+// ```
+
+#[derive(SmartClone, PartialEq, Debug)]
+enum Msg<'a, T, const N: usize> {
+    Text(&'a str),
+    Bytes([u8; N]),
+    #[clone(value = Msg::Text("resource"))]
+    Resource(T), // overridden: no `T: Clone` required
+}
+
+fn main() {
+    let cache: Cache<u8, Handle, Handle> = Cache {
+        keys: vec![1, 2, 3],
+        values: vec![Handle(1), Handle(2)],
+        marker: PhantomData,
+    };
+    assert_eq!(
+        cache.clone(),
+        Cache {
+            keys: vec![1, 2, 3],
+            values: vec![],
+            marker: PhantomData,
+        }
+    );
+
+    let shared = Shared {
+        config: Arc::new(Handle(3)),
+        scratch: Handle(4),
+    };
+    let copy = shared.clone();
+    assert!(Arc::ptr_eq(&copy.config, &shared.config));
+    assert_eq!(copy.scratch, Handle(0));
+
+    let text: Msg<Handle, 2> = Msg::Text("hello");
+    assert_eq!(text.clone(), Msg::Text("hello"));
+
+    let bytes: Msg<Handle, 2> = Msg::Bytes([4, 2]);
+    assert_eq!(bytes.clone(), Msg::Bytes([4, 2]));
+
+    let resource: Msg<Handle, 2> = Msg::Resource(Handle(7));
+    assert_eq!(resource.clone(), Msg::Text("resource"));
+}
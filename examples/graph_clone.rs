@@ -0,0 +1,74 @@
+use std::cell::RefCell;
+use std::rc::{Rc, Weak};
+
+use smart_clone::GraphClone;
+
+#[derive(GraphClone, Debug)]
+struct Node {
+    name: String,
+    parent: RefCell<Weak<Node>>,
+    children: RefCell<Vec<Rc<Node>>>,
+}
+
+// Will be expanded to :
+// ```
+// impl Node {
+//     fn clone_graph(&self) -> Self {
+//         self.clone_graph_in(&mut HashMap::new())
+//     }
+//     fn clone_graph_in(&self, memo: &mut HashMap<usize, Box<dyn Any>>) -> Self {
+//         Self {
+//             name: self.name.clone(),
+//             parent: /* the new parent, found in the memo table */,
+//             children: /* each child cloned once, with `Rc::new_cyclic` */,
+//         }
+//     }
+// }
+// ```
+
+#[derive(GraphClone, Debug)]
+struct Scene {
+    root: Rc<Node>,
+    selection: Vec<Rc<Node>>,
+}
+
+fn node(name: &str, parent: &Rc<Node>) -> Rc<Node> {
+    let node = Rc::new(Node {
+        name: String::from(name),
+        parent: RefCell::new(Rc::downgrade(parent)),
+        children: RefCell::new(vec![]),
+    });
+    parent.children.borrow_mut().push(node.clone());
+    node
+}
+
+fn main() {
+    let root = Rc::new(Node {
+        name: String::from("root"),
+        parent: RefCell::new(Weak::new()),
+        children: RefCell::new(vec![]),
+    });
+    let camera = node("camera", &root);
+    let light = node("light", &root);
+    let scene = Scene {
+        root: root.clone(),
+        selection: vec![camera.clone(), light.clone(), camera.clone()],
+    };
+
+    let copy = scene.clone_graph();
+
+    // Nothing is shared with the original graph.
+    assert!(!Rc::ptr_eq(&copy.root, &scene.root));
+    assert!(!Rc::ptr_eq(&copy.selection[0], &camera));
+
+    // Each node is cloned once: the sharing is kept in the copy.
+    let children = copy.root.children.borrow();
+    assert!(Rc::ptr_eq(&copy.selection[0], &children[0]));
+    assert!(Rc::ptr_eq(&copy.selection[1], &children[1]));
+    assert!(Rc::ptr_eq(&copy.selection[0], &copy.selection[2]));
+
+    // The parent links point at the new root.
+    let parent = children[0].parent.borrow().upgrade().unwrap();
+    assert!(Rc::ptr_eq(&parent, &copy.root));
+    assert_eq!(children[1].name, "light");
+}
@@ -0,0 +1,107 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use smart_clone::SmartClone;
+
+#[derive(SmartClone)]
+#[clone(into = EditorSnapshot, derive(Debug, PartialEq))]
+pub struct Editor {
+    pub text: String,
+    pub cursor: usize,
+    #[clone(exclude, default)]
+    render_cache: HashMap<usize, String>,
+    #[clone(exclude, with = Editor::new_lock)]
+    lock: Mutex<()>,
+}
+
+impl Editor {
+    fn new_lock(_: &Mutex<()>) -> Mutex<()> {
+        Mutex::new(())
+    }
+}
+
+#[derive(SmartClone, Debug)]
+#[clone(into = ActionSnapshot, derive(Debug, PartialEq))]
+enum Action<T> {
+    Insert(usize, T),
+    Delete {
+        at: usize,
+        #[clone(exclude)]
+        undo: Vec<String>,
+    },
+    Save,
+}
+
+// Will be expanded to :
+// ```
+// impl Clone for Editor { ... }
+// #[derive(Debug, PartialEq)]
+// pub struct EditorSnapshot {
+//     pub text: String,
+//     pub cursor: usize,
+// }
+// impl From<&Editor> for EditorSnapshot {
+//     fn from(value: &Editor) -> Self {
+//         EditorSnapshot {
+//             text: value.text.clone(),
+//             cursor: value.cursor.clone(),
+//         }
+//     }
+// }
+//
+// impl<T: Clone> Clone for Action<T> { ... }
+// #[derive(Debug, PartialEq)]
+// enum ActionSnapshot<T> {
+//     Insert(usize, T),
+//     Delete { at: usize },
+//     Save,
+// }
+// impl<T: Clone> From<&Action<T>> for ActionSnapshot<T> {
+//     fn from(value: &Action<T>) -> Self {
+//         match value {
+//             Action::Insert(v0, v1) => ActionSnapshot::Insert(v0.clone(), v1.clone()),
+//             Action::Delete { at, .. } => ActionSnapshot::Delete { at: at.clone() },
+//             Action::Save => ActionSnapshot::Save,
+//         }
+//     }
+// }
+// ```
+
+fn main() {
+    let editor = Editor {
+        text: String::from("Hello"),
+        cursor: 5,
+        render_cache: HashMap::from([(0, String::from("<p>Hello</p>"))]),
+        lock: Mutex::new(()),
+    };
+
+    // The snapshot holds neither the cache nor the lock.
+    let snapshot = EditorSnapshot::from(&editor);
+    assert_eq!(
+        snapshot,
+        EditorSnapshot {
+            text: String::from("Hello"),
+            cursor: 5,
+        }
+    );
+
+    let undo_stack: Vec<ActionSnapshot<char>> = [
+        Action::Insert(5, '!'),
+        Action::Delete {
+            at: 5,
+            undo: vec![String::from("!")],
+        },
+        Action::Save,
+    ]
+    .iter()
+    .map(ActionSnapshot::from)
+    .collect();
+    assert_eq!(
+        undo_stack,
+        [
+            ActionSnapshot::Insert(5, '!'),
+            ActionSnapshot::Delete { at: 5 },
+            ActionSnapshot::Save,
+        ]
+    );
+}
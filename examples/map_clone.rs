@@ -0,0 +1,91 @@
+use std::rc::Rc;
+
+use smart_clone::SmartClone;
+
+#[derive(SmartClone, Debug, PartialEq)]
+struct Document {
+    #[clone(map = |name: String| name + " (copy)")] // Tweak the usual clone.
+    name: String,
+    #[clone(map = bump)]
+    version: u32,
+    #[clone(deep, map = Document::sorted)] // Applied to the deep clone.
+    tags: Vec<Rc<String>>,
+}
+
+impl Document {
+    fn sorted(mut tags: Vec<Rc<String>>) -> Vec<Rc<String>> {
+        tags.sort();
+        tags
+    }
+}
+
+fn bump(version: u32) -> u32 {
+    version + 1
+}
+
+#[derive(SmartClone, Debug, PartialEq)]
+enum Entry {
+    #[clone(map = Entry::archived)] // Applied to the whole cloned variant.
+    File {
+        name: String,
+        size: u64,
+    },
+    Archive(String, #[clone(map = bump)] u32),
+}
+
+impl Entry {
+    fn archived(self) -> Self {
+        match self {
+            Entry::File { name, .. } => Entry::Archive(name, 0),
+            other => other,
+        }
+    }
+}
+
+// Will be expanded to :
+// ```
+// impl Clone for Document {
+//     fn clone(&self) -> Self {
+//         Self {
+//             name: (|name: String| name + " (copy)")(self.name.clone()),
+//             version: bump(self.version.clone()),
+//             tags: Document::sorted(/* deep clone of self.tags */),
+//         }
+//     }
+//     fn clone_from(&mut self, source: &Self) { ... }
+// }
+//
+// impl Clone for Entry {
+//     fn clone(&self) -> Self {
+//         match self {
+//             Entry::File { name, size } => Entry::archived(Entry::File { name: name.clone(), size: size.clone() }),
+//             Entry::Archive(v0, v1) => Entry::Archive(v0.clone(), bump(v1.clone())),
+//         }
+//     }
+//     fn clone_from(&mut self, source: &Self) { ... }
+// }
+// ```
+
+fn main() {
+    let document = Document {
+        name: String::from("report"),
+        version: 3,
+        tags: vec![Rc::new(String::from("b")), Rc::new(String::from("a"))],
+    };
+    let clone = document.clone();
+    assert_eq!(clone.name, "report (copy)");
+    assert_eq!(clone.version, 4);
+    assert_eq!(
+        clone.tags,
+        vec![Rc::new(String::from("a")), Rc::new(String::from("b"))]
+    );
+    assert!(!Rc::ptr_eq(&clone.tags[1], &document.tags[0]));
+
+    let file = Entry::File {
+        name: String::from("notes.txt"),
+        size: 42,
+    };
+    assert_eq!(file.clone(), Entry::Archive(String::from("notes.txt"), 0));
+    let archive = Entry::Archive(String::from("backup"), 1);
+    assert_eq!(archive.clone(), Entry::Archive(String::from("backup"), 2));
+}
@@ -0,0 +1,108 @@
+use smart_clone::SmartClone;
+
+#[derive(SmartClone, PartialEq, Debug)]
+#[clone(mask)]
+struct Profile {
+    #[clone(keep)]
+    id: u64,
+    name: String,
+    email: Option<String>,
+    #[clone(value = String::from("hidden"))]
+    phone: String,
+    tags: Vec<String>,
+}
+
+// Will be expanded to :
+// ```
+// impl Clone for Profile { ... }
+// #[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+// enum ProfileField {
+//     Name,
+//     Email,
+//     Phone,
+//     Tags,
+// }
+// #[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Default)]
+// struct ProfileFieldSet { ... }
+// impl Profile {
+//     fn clone_except(&self, mask: ProfileFieldSet) -> Self {
+//         Self {
+//             id: self.id.clone(),
+//             name: match mask.contains(ProfileField::Name) {
+//                 true => <String as Default>::default(),
+//                 false => self.name.clone(),
+//             },
+//             ...
+//             phone: match mask.contains(ProfileField::Phone) {
+//                 true => String::from("hidden"),
+//                 false => String::from("hidden"),
+//             },
+//             ...
+//         }
+//     }
+//     fn clone_only(&self, fields: ProfileFieldSet) -> Self {
+//         self.clone_except(fields.complement())
+//     }
+// }
+// ```
+
+/// Hide all the digits of a card number but the last four.
+fn mask(number: &str) -> String {
+    let visible = number.len().saturating_sub(4);
+    format!("{}{}", "*".repeat(visible), &number[visible..])
+}
+
+// The overrides can call an item named `mask`, like the parameter of `clone_except`.
+#[derive(SmartClone)]
+#[clone(mask)]
+struct Payment {
+    #[clone(value = mask(&self.card))]
+    card: String,
+    amount: u64,
+}
+
+fn main() {
+    let profile = Profile {
+        id: 7,
+        name: String::from("Ada"),
+        email: Some(String::from("ada@example.com")),
+        phone: String::from("555-0100"),
+        tags: vec![String::from("admin")],
+    };
+
+    // The mask is decided at runtime, from the settings of the user.
+    let share_email = false;
+    let mask = match share_email {
+        true => ProfileFieldSet::empty(),
+        false => ProfileField::Email.into(),
+    };
+    let shared = profile.clone_except(mask);
+    assert_eq!(shared.id, 7);
+    assert_eq!(shared.name, "Ada");
+    assert_eq!(shared.email, None);
+    assert_eq!(shared.tags, profile.tags);
+
+    // Masked fields with an override are reset to it.
+    let masked = profile.clone_except(ProfileField::Phone | ProfileField::Tags);
+    assert_eq!(masked.phone, "hidden");
+    assert!(masked.tags.is_empty());
+
+    // `clone_only` resets the other fields, except the ones marked with `keep`.
+    let public = profile.clone_only(ProfileField::Name.into());
+    assert_eq!((public.id, public.name.as_str()), (7, "Ada"));
+    assert_eq!(public.email, None);
+    assert!(public.tags.is_empty());
+
+    assert_eq!(
+        profile.clone_except(ProfileFieldSet::empty()),
+        profile.clone()
+    );
+
+    let payment = Payment {
+        card: String::from("4111111111111111"),
+        amount: 42,
+    };
+    let receipt = payment.clone_except(PaymentField::Amount.into());
+    assert_eq!(receipt.card, "************1111");
+    assert_eq!(receipt.amount, 0);
+}
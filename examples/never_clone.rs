@@ -0,0 +1,78 @@
+use std::panic;
+
+use smart_clone::{SmartClone, SmartTryClone};
+
+/// A lock guard, which must not be duplicated.
+#[derive(Debug, PartialEq)]
+struct Lock(u32);
+
+#[derive(SmartClone, SmartTryClone, Debug, PartialEq)]
+enum Transaction {
+    Idle,
+    #[clone(panic = "commit or roll back the transaction first")]
+    InFlight(u64),
+    #[clone(forbid)] // `try_clone` returns an error instead of panicking.
+    Committing { id: u64 },
+    Done {
+        id: u64,
+        #[clone(unreachable)] // A lock is released before the transaction is done.
+        lock: Option<Lock>,
+    },
+}
+
+// Will be expanded to :
+// ```
+// impl Clone for Transaction {
+//     fn clone(&self) -> Self {
+//         match self {
+//             Transaction::Idle => Transaction::Idle,
+//             Transaction::InFlight(..) => panic!(
+//                 "cannot clone variant `{}::InFlight`: {}",
+//                 type_name::<Self>(),
+//                 "commit or roll back the transaction first"
+//             ),
+//             Transaction::Committing { .. } => panic!("cannot clone variant `{}::Committing`", type_name::<Self>()),
+//             Transaction::Done { .. } => unreachable!("cannot clone field `lock` of `{}::Done`", type_name::<Self>()),
+//         }
+//     }
+// }
+//
+// impl Transaction {
+//     fn try_clone(&self) -> Result<Self, Box<dyn Error + Send + Sync>> {
+//         Ok(match self {
+//             ...
+//             Transaction::Committing { .. } => {
+//                 return Err(From::from(format!("cannot clone variant `{}::Committing`", type_name::<Self>())))
+//             }
+//             ...
+//         })
+//     }
+// }
+// ```
+
+fn main() {
+    assert_eq!(Transaction::Idle.clone(), Transaction::Idle);
+    assert_eq!(Transaction::Idle.try_clone().unwrap(), Transaction::Idle);
+
+    let committing = Transaction::Committing { id: 7 };
+    let error = committing.try_clone().unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "cannot clone variant `never_clone::Transaction::Committing`"
+    );
+
+    // The other variants panic with a message naming what cannot be cloned.
+    panic::set_hook(Box::new(|_| {}));
+    let in_flight = Transaction::InFlight(7);
+    let message = *panic::catch_unwind(|| in_flight.clone())
+        .unwrap_err()
+        .downcast::<String>()
+        .unwrap();
+    assert_eq!(
+        message,
+        "cannot clone variant `never_clone::Transaction::InFlight`: commit or roll back the transaction first"
+    );
+    assert!(panic::catch_unwind(|| committing.clone()).is_err());
+    let done = Transaction::Done { id: 7, lock: None };
+    assert!(panic::catch_unwind(|| done.try_clone()).is_err());
+}
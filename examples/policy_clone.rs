@@ -0,0 +1,128 @@
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::Instant;
+
+use smart_clone::SmartClone;
+
+#[derive(SmartClone, Debug, PartialEq)]
+#[clone(default_fields = "cache_*, *_scratch")] // Transient fields follow a naming convention.
+struct Renderer {
+    name: String,
+    cache_glyphs: Vec<char>,
+    cache_layout: Vec<u32>,
+    #[clone] // Its own attribute takes priority: cloned as usual.
+    cache_shared: Arc<Vec<u8>>,
+    line_scratch: String,
+}
+
+#[derive(SmartClone, Debug, PartialEq)]
+#[clone(all = default, except(id))]
+struct Request {
+    id: u64,
+    retries: u32,
+    body: Vec<u8>,
+    #[clone(value = String::from("cloned"))]
+    origin: String,
+}
+
+#[derive(SmartClone, Debug)]
+#[clone(
+    for_type(std::time::Instant = "Instant::now()"),
+    for_type(Option<JoinHandle<()>> = default)
+)]
+struct Worker {
+    name: String,
+    started: Instant,
+    #[clone] // Its own attribute takes priority: cloned as usual.
+    deadline: Instant,
+    handle: Option<JoinHandle<()>>,
+}
+
+// Will be expanded to :
+// ```
+// impl Clone for Renderer {
+//     fn clone(&self) -> Self {
+//         Self {
+//             name: self.name.clone(),
+//             cache_glyphs: Default::default(),
+//             cache_layout: Default::default(),
+//             cache_shared: self.cache_shared.clone(),
+//             line_scratch: Default::default(),
+//         }
+//     }
+//     fn clone_from(&mut self, source: &Self) { ... }
+// }
+//
+// impl Clone for Request {
+//     fn clone(&self) -> Self {
+//         Self {
+//             id: self.id.clone(),
+//             retries: Default::default(),
+//             body: Default::default(),
+//             origin: String::from("cloned"),
+//         }
+//     }
+//     fn clone_from(&mut self, source: &Self) { ... }
+// }
+//
+// impl Clone for Worker {
+//     fn clone(&self) -> Self {
+//         Self {
+//             name: self.name.clone(),
+//             started: Instant::now(),
+//             deadline: self.deadline.clone(),
+//             handle: Default::default(),
+//         }
+//     }
+//     fn clone_from(&mut self, source: &Self) { ... }
+// }
+// ```
+
+fn main() {
+    let renderer = Renderer {
+        name: String::from("main"),
+        cache_glyphs: vec!['a', 'b'],
+        cache_layout: vec![1, 2, 3],
+        cache_shared: Arc::new(vec![4, 5]),
+        line_scratch: String::from("scratch"),
+    };
+    assert_eq!(
+        renderer.clone(),
+        Renderer {
+            name: String::from("main"),
+            cache_glyphs: vec![],
+            cache_layout: vec![],
+            cache_shared: Arc::new(vec![4, 5]),
+            line_scratch: String::new(),
+        }
+    );
+
+    let request = Request {
+        id: 42,
+        retries: 3,
+        body: vec![1, 2, 3],
+        origin: String::from("client"),
+    };
+    assert_eq!(
+        request.clone(),
+        Request {
+            id: 42,
+            retries: 0,
+            body: vec![],
+            origin: String::from("cloned"),
+        }
+    );
+
+    let worker = Worker {
+        name: String::from("indexer"),
+        started: Instant::now(),
+        deadline: Instant::now(),
+        handle: Some(thread::spawn(|| {})),
+    };
+    let clone = worker.clone();
+    assert_eq!(clone.name, "indexer");
+    assert!(clone.started >= worker.started);
+    assert_eq!(clone.deadline, worker.deadline);
+    assert!(clone.handle.is_none());
+    worker.handle.unwrap().join().unwrap();
+}
@@ -0,0 +1,74 @@
+use smart_clone::SmartClone;
+
+#[derive(SmartClone, PartialEq, Debug)]
+struct Document {
+    #[clone(profile = "template", default)]
+    id: u64,
+    title: String,
+    #[clone(profile = "template", default)]
+    created_at: u64,
+    #[clone(default)] // `Clone` drops the cache...
+    #[clone(profile = "snapshot")] // ... but a snapshot keeps it.
+    cache: Vec<String>,
+    #[clone(profile = "persist", value = None)]
+    handle: Option<u32>,
+}
+
+// Will be expanded to :
+// ```
+// impl Clone for Document {
+//     fn clone(&self) -> Self {
+//         Self {
+//             id: self.id.clone(),
+//             title: self.title.clone(),
+//             created_at: self.created_at.clone(),
+//             cache: Default::default(),
+//             handle: self.handle.clone(),
+//         }
+//     }
+//     fn clone_from(&mut self, source: &Self) { ... }
+// }
+// impl Document {
+//     fn clone_template(&self) -> Self {
+//         Self {
+//             id: Default::default(),
+//             title: self.title.clone(),
+//             created_at: Default::default(),
+//             cache: Default::default(),
+//             handle: self.handle.clone(),
+//         }
+//     }
+// }
+// impl Document {
+//     fn clone_snapshot(&self) -> Self { ... }
+// }
+// impl Document {
+//     fn clone_persist(&self) -> Self { ... }
+// }
+// ```
+
+fn main() {
+    let document = Document {
+        id: 42,
+        title: String::from("Report"),
+        created_at: 1_700_000_000,
+        cache: vec![String::from("rendered")],
+        handle: Some(3),
+    };
+
+    let cloned = document.clone();
+    assert_eq!(cloned.id, 42);
+    assert!(cloned.cache.is_empty());
+
+    let template = document.clone_template();
+    assert_eq!((template.id, template.created_at), (0, 0));
+    assert_eq!(template.title, "Report");
+    assert!(template.cache.is_empty());
+
+    let snapshot = document.clone_snapshot();
+    assert_eq!(snapshot, document);
+
+    let persisted = document.clone_persist();
+    assert_eq!(persisted.handle, None);
+    assert!(persisted.cache.is_empty());
+}
@@ -0,0 +1,61 @@
+use smart_clone::SmartClone;
+
+#[derive(Debug, PartialEq)]
+struct Handle(u32);
+
+#[derive(SmartClone, Debug, PartialEq)]
+enum Task<J> {
+    Pending {
+        job: J,
+        retries: u32,
+    },
+    #[clone(to = Pending)] // A running task is cloned as a pending one, without its handle.
+    Running {
+        job: J,
+        handle: Handle, // Not `Clone`: it is never cloned.
+    },
+    #[clone(to = Idle)]
+    Failed(String),
+    Idle,
+}
+
+// Will be expanded to :
+// ```
+// impl<J: Clone> Clone for Task<J> {
+//     fn clone(&self) -> Self {
+//         match self {
+//             Task::Pending { job, retries } => Task::Pending { job: job.clone(), retries: retries.clone() },
+//             Task::Running { job, .. } => Task::Pending {
+//                 job: job.clone(),
+//                 retries: <u32 as Default>::default(),
+//             },
+//             Task::Failed(_) => Task::Idle,
+//             Task::Idle => Task::Idle,
+//         }
+//     }
+//     fn clone_from(&mut self, source: &Self) { ... }
+// }
+// ```
+
+fn main() {
+    let running = Task::Running {
+        job: String::from("backup"),
+        handle: Handle(42),
+    };
+    assert_eq!(
+        running.clone(),
+        Task::Pending {
+            job: String::from("backup"),
+            retries: 0,
+        }
+    );
+
+    let failed: Task<String> = Task::Failed(String::from("disk full"));
+    assert_eq!(failed.clone(), Task::Idle);
+
+    let pending = Task::Pending {
+        job: String::from("backup"),
+        retries: 2,
+    };
+    assert_eq!(pending.clone(), pending);
+}
@@ -0,0 +1,74 @@
+use smart_clone::SmartClone;
+
+fn checksum(payload: &[u8]) -> u32 {
+    payload.iter().map(|byte| *byte as u32).sum()
+}
+
+#[derive(SmartClone, PartialEq, Debug)]
+struct Node {
+    id: u32,
+    #[clone(with_parent = Node::derive_label)]
+    label: String,
+    #[clone(value = self.generation + 1)]
+    generation: u32,
+}
+
+impl Node {
+    fn derive_label(label: &str, parent: &Node) -> String {
+        format!("{} (copy of #{})", label, parent.id)
+    }
+}
+
+// Will be expanded to :
+// ```
+// impl Clone for Node {
+//     fn clone(&self) -> Self {
+//         Self {
+//             id: self.id.clone(),
+//             label: Node::derive_label(&self.label, self),
+//             generation: self.generation + 1,
+//         }
+//     }
+//     fn clone_from(&mut self, source: &Self) { ... }
+// }
+// ```
+
+#[derive(SmartClone, PartialEq, Debug)]
+struct Packet {
+    #[clone(with = |payload: &Vec<u8>| payload.iter().rev().cloned().collect())]
+    payload: Vec<u8>,
+    #[clone(value = checksum(&payload), uses(payload))]
+    crc: u32,
+}
+
+// Will be expanded to :
+// ```
+// impl Clone for Packet {
+//     fn clone(&self) -> Self {
+//         {
+//             let payload: Vec<u8> = (|payload: &Vec<u8>| payload.iter().rev().cloned().collect())(&self.payload);
+//             let crc: u32 = checksum(&payload);
+//             Self { payload, crc }
+//         }
+//     }
+// }
+// ```
+
+fn main() {
+    let node = Node {
+        id: 7,
+        label: String::from("root"),
+        generation: 1,
+    };
+    let cloned = node.clone();
+    assert_eq!(cloned.label, "root (copy of #7)");
+    assert_eq!(cloned.generation, 2);
+
+    let packet = Packet {
+        payload: vec![1, 2, 3],
+        crc: 0,
+    };
+    let cloned = packet.clone();
+    assert_eq!(cloned.payload, vec![3, 2, 1]);
+    assert_eq!(cloned.crc, 6);
+}
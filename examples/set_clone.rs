@@ -0,0 +1,69 @@
+use smart_clone::SmartClone;
+
+/// Types of another crate, which only derive `Clone`.
+mod settings {
+    #[derive(Clone, Debug, PartialEq)]
+    pub struct Session {
+        pub token: Option<String>,
+        pub retries: u32,
+    }
+
+    #[derive(Clone, Debug, PartialEq)]
+    pub struct Settings {
+        pub theme: String,
+        pub session: Session,
+    }
+}
+
+use settings::{Session, Settings};
+
+#[derive(SmartClone, Debug, PartialEq)]
+struct Client {
+    name: String,
+    #[clone(set(session.token = None, session.retries = 0))] // Clone the settings, without the session.
+    settings: Settings,
+}
+
+// Will be expanded to :
+// ```
+// impl Clone for Client {
+//     fn clone(&self) -> Self {
+//         Self {
+//             name: self.name.clone(),
+//             settings: {
+//                 let mut value = self.settings.clone();
+//                 value.session.token = None;
+//                 value.session.retries = 0;
+//                 value
+//             },
+//         }
+//     }
+//     fn clone_from(&mut self, source: &Self) { ... }
+// }
+// ```
+
+fn main() {
+    let client = Client {
+        name: String::from("desktop"),
+        settings: Settings {
+            theme: String::from("dark"),
+            session: Session {
+                token: Some(String::from("secret")),
+                retries: 2,
+            },
+        },
+    };
+    assert_eq!(
+        client.clone(),
+        Client {
+            name: String::from("desktop"),
+            settings: Settings {
+                theme: String::from("dark"),
+                session: Session {
+                    token: None,
+                    retries: 0,
+                },
+            },
+        }
+    );
+}
@@ -7,19 +7,21 @@ struct SimpleStruct {
     c: Option<i32>, // left for standard clone
     d: Vec<u32>,    // left for standard clone
     #[clone = 12]
-    e: u8, // Override with hardcoded value (same as `#[clone(12)]`)
-    #[clone(TEST)]
+    e: u8, // Override with hardcoded value (same as `#[clone(value = 12)]`)
+    #[clone(value = TEST)]
     f: u8, // In general, prefer this syntax for hardcode, variable or const
-    #[clone((42, 69))]
+    #[clone(value = (42, 69))]
     g: (i32, u32),
     #[clone(default)]
     h: Vec<Vec<Vec<(u8, u8)>>>, // Reserved 'skip' keyword to clone to Default::default() value (g type must implement `Default`)
-    #[clone(Some(Default::default()))] // `Some(Default::default())` is not `None` but `Some(0)` !
+    #[clone(value = Some(Default::default()))] // `Some(Default::default())` is not `None` but `Some(0)` !
     i: Option<i32>,
-    #[clone(clone_with = "SimpleStruct::vec_clone")]
+    #[clone(with = SimpleStruct::vec_clone)]
     j: Vec<u32>,
-    #[clone("banana".to_owned())]
+    #[clone(value = "banana".to_owned())]
     k: String,
+    #[clone(with = |v: &Vec<u32>| v.iter().rev().cloned().collect())]
+    l: Vec<u32>,
 }
 
 const TEST: u8 = 3;
@@ -40,19 +42,20 @@ const TEST: u8 = 3;
 //             i: Some(Default::default()),
 //             j: SimpleStruct::vec_clone(&self.j),
 //             k: "banana".to_owned(),
+//             l: (|v: &Vec<u32>| v.iter().rev().cloned().collect())(&self.l),
 //         }
 //     }
 // }
 // ```
 
 impl SimpleStruct {
-    fn vec_clone(input: &Vec<u32>) -> Vec<u32> {
+    fn vec_clone(input: &[u32]) -> Vec<u32> {
         input.iter().map(|i| i * 2).collect()
     }
 }
 
 fn main() {
-    let foo = SimpleStruct {
+    let simple = SimpleStruct {
         a: 42,
         b: (8, 8),
         c: Some(42),
@@ -64,9 +67,10 @@ fn main() {
         i: Some(24),
         j: vec![1, 2, 3],
         k: "apple".to_string(),
+        l: vec![1, 2, 3],
     };
     assert_eq!(
-        foo.clone(),
+        simple.clone(),
         SimpleStruct {
             a: 42,
             b: (8, 8),
@@ -79,6 +83,7 @@ fn main() {
             i: Some(0),
             j: vec![2, 4, 6],
             k: String::from("banana"),
+            l: vec![3, 2, 1],
         }
     );
 }
@@ -0,0 +1,100 @@
+use std::fmt;
+
+use smart_clone::SmartTryClone;
+
+/// A handle which can only be duplicated a limited number of times, like an FFI resource.
+#[derive(Debug, PartialEq)]
+struct Handle {
+    id: u32,
+}
+
+#[derive(Debug, PartialEq)]
+struct ExhaustedError(u32);
+
+impl fmt::Display for ExhaustedError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "handle {} cannot be duplicated", self.0)
+    }
+}
+
+impl std::error::Error for ExhaustedError {}
+
+impl Handle {
+    fn try_duplicate(&self) -> Result<Handle, ExhaustedError> {
+        match self.id < 10 {
+            true => Ok(Handle { id: self.id + 1 }),
+            false => Err(ExhaustedError(self.id)),
+        }
+    }
+}
+
+#[derive(SmartTryClone, Debug, PartialEq)]
+#[clone(error = ExhaustedError)]
+struct Connection {
+    name: String,
+    #[clone(try_with = Handle::try_duplicate)]
+    handle: Handle,
+}
+
+// Will be expanded to :
+// ```
+// impl Connection {
+//     fn try_clone(&self) -> Result<Self, ExhaustedError> {
+//         Ok(Self {
+//             name: self.name.clone(),
+//             handle: Handle::try_duplicate(&self.handle)?,
+//         })
+//     }
+// }
+// ```
+
+#[derive(SmartTryClone, Debug, PartialEq)]
+enum Resource {
+    Named(String),
+    Opened {
+        #[clone(try_with = Handle::try_duplicate)]
+        handle: Handle,
+    },
+}
+
+// Will be expanded to :
+// ```
+// impl Resource {
+//     fn try_clone(&self) -> Result<Self, Box<dyn Error + Send + Sync>> {
+//         Ok(match self {
+//             Resource::Named(v0) => Resource::Named(v0.clone()),
+//             Resource::Opened { handle } => Resource::Opened {
+//                 handle: Handle::try_duplicate(handle).map_err(|error| ...)?,
+//             },
+//         })
+//     }
+// }
+// ```
+
+fn main() {
+    let connection = Connection {
+        name: String::from("db"),
+        handle: Handle { id: 1 },
+    };
+    let cloned = connection.try_clone().unwrap();
+    assert_eq!(cloned.name, "db");
+    assert_eq!(cloned.handle, Handle { id: 2 });
+
+    let exhausted = Connection {
+        name: String::from("db"),
+        handle: Handle { id: 10 },
+    };
+    assert_eq!(exhausted.try_clone(), Err(ExhaustedError(10)));
+
+    let named = Resource::Named(String::from("file"));
+    assert_eq!(named.try_clone().unwrap(), named);
+
+    let opened = Resource::Opened {
+        handle: Handle { id: 10 },
+    };
+    let error = opened.try_clone().unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "failed to clone field `handle`: handle 10 cannot be duplicated"
+    );
+}
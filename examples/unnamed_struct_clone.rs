@@ -4,12 +4,12 @@ use smart_clone::SmartClone;
 struct Point4D(
     i32,
     #[clone(default)] i32,
-    #[clone(String::from("banana"))] String,
+    #[clone(value = String::from("banana"))] String,
     #[clone(clone_with = "Point4D::vec_clone")] Vec<u32>,
 );
 
 impl Point4D {
-    fn vec_clone(input: &Vec<u32>) -> Vec<u32> {
+    fn vec_clone(input: &[u32]) -> Vec<u32> {
         input.iter().map(|i| i * 2).collect()
     }
 }
@@ -0,0 +1,527 @@
+use proc_macro2::{Ident, Span};
+use quote::ToTokens;
+use syn::ext::IdentExt;
+use syn::{DeriveInput, Fields, Generics, Index, Member, Type, Visibility};
+
+use crate::internals::attr::{same_type, ContainerAttrs, Errors, FieldAttrs, Strategy, Target};
+
+/// A struct or an enum, along with its `#[clone...]` options.
+pub(crate) struct Container<'a> {
+    pub ident: &'a Ident,
+    pub vis: &'a Visibility,
+    pub generics: &'a Generics,
+    pub attrs: ContainerAttrs,
+    pub data: Data<'a>,
+}
+
+/// The body of a struct or an enum.
+pub(crate) enum Data<'a> {
+    Struct(Style, Vec<Field<'a>>),
+    Enum(Vec<Variant<'a>>),
+}
+
+/// An enum variant, along with its `#[clone...]` options.
+pub(crate) struct Variant<'a> {
+    pub ident: &'a Ident,
+    pub attrs: FieldAttrs,
+    pub style: Style,
+    pub fields: Vec<Field<'a>>,
+}
+
+/// A field of a struct or of an enum variant, along with its `#[clone...]` options.
+pub(crate) struct Field<'a> {
+    pub member: Member,
+    pub vis: &'a Visibility,
+    pub ty: &'a Type,
+    pub attrs: FieldAttrs,
+}
+
+/// The kind of fields of a struct or an enum variant.
+#[derive(Clone, Copy, PartialEq)]
+pub(crate) enum Style {
+    /// `Point { x: u8, y: u8 }`
+    Named,
+    /// `Point(u8, u8)`
+    Unnamed,
+    /// `Point`
+    Unit,
+}
+
+impl<'a> Container<'a> {
+    /// Read a struct or an enum and its `#[clone...]` attributes.
+    pub fn from_ast(input: &'a DeriveInput, errors: &mut Errors) -> Option<Self> {
+        let attrs = ContainerAttrs::from_ast(&input.attrs, errors);
+        let mut data = match &input.data {
+            syn::Data::Struct(data_struct) => {
+                let (style, fields) = fields_from_ast(&data_struct.fields, errors);
+                check_uses(style, &fields, errors);
+                Data::Struct(style, fields)
+            }
+            syn::Data::Enum(data_enum) => Data::Enum(
+                data_enum
+                    .variants
+                    .iter()
+                    .map(|variant| {
+                        let (style, fields) = fields_from_ast(&variant.fields, errors);
+                        for used in fields.iter().flat_map(|field| &field.attrs.uses) {
+                            errors.push(syn::Error::new_spanned(
+                                used,
+                                "`uses` is only supported on the fields of a struct",
+                            ));
+                        }
+                        Variant {
+                            ident: &variant.ident,
+                            attrs: FieldAttrs::from_ast(&variant.attrs, Target::Variant, errors),
+                            style,
+                            fields,
+                        }
+                    })
+                    .collect(),
+            ),
+            syn::Data::Union(data_union) => {
+                errors.push(syn::Error::new_spanned(
+                    data_union.union_token,
+                    "Cannot use SmartClone on union types.",
+                ));
+                return None;
+            }
+        };
+
+        apply_policies(&attrs, &mut data, errors);
+        if let Data::Enum(variants) = &data {
+            check_remaps(variants, errors);
+        }
+        if attrs.context.is_none() {
+            check_without_context(&data, errors);
+        }
+        if let Some(builder) = &attrs.builder {
+            check_builder(builder, &data, errors);
+        }
+        check_mask(attrs.mask.as_ref(), &data, errors);
+        check_into(attrs.into.as_ref(), &data, errors);
+
+        Some(Container {
+            ident: &input.ident,
+            vis: &input.vis,
+            generics: &input.generics,
+            attrs,
+            data,
+        })
+    }
+}
+
+impl Container<'_> {
+    /// The names of the profiles set on the fields and the variants, in order of appearance.
+    pub fn profiles(&self) -> Vec<&Ident> {
+        let attrs: Vec<&FieldAttrs> = match &self.data {
+            Data::Struct(_, fields) => fields.iter().map(|field| &field.attrs).collect(),
+            Data::Enum(variants) => variants
+                .iter()
+                .flat_map(|variant| {
+                    std::iter::once(&variant.attrs)
+                        .chain(variant.fields.iter().map(|field| &field.attrs))
+                })
+                .collect(),
+        };
+        let mut profiles: Vec<&Ident> = Vec::new();
+        for profile in attrs.into_iter().flat_map(|attrs| &attrs.profiles) {
+            if !profiles.contains(&&profile.name) {
+                profiles.push(&profile.name);
+            }
+        }
+        profiles
+    }
+}
+
+impl Field<'_> {
+    /// The name under which the field is bound when matching an enum variant: `x` for named
+    /// fields, `v0`, `v1`... for unnamed ones.
+    pub fn binding(&self) -> Ident {
+        match &self.member {
+            Member::Named(ident) => ident.clone(),
+            Member::Unnamed(index) => Ident::new(&format!("v{}", index.index), Span::call_site()),
+        }
+    }
+}
+
+/// Read the fields of a struct or of an enum variant.
+fn fields_from_ast<'a>(fields: &'a Fields, errors: &mut Errors) -> (Style, Vec<Field<'a>>) {
+    let style = match fields {
+        Fields::Named(_) => Style::Named,
+        Fields::Unnamed(_) => Style::Unnamed,
+        Fields::Unit => Style::Unit,
+    };
+    let fields = fields
+        .iter()
+        .enumerate()
+        .map(|(i, field)| Field {
+            member: match &field.ident {
+                Some(ident) => Member::Named(ident.clone()),
+                None => Member::Unnamed(Index::from(i)),
+            },
+            vis: &field.vis,
+            ty: &field.ty,
+            attrs: FieldAttrs::from_ast(&field.attrs, Target::Field, errors),
+        })
+        .collect();
+    (style, fields)
+}
+
+/// Give their strategy to the fields selected by the policies of the struct or enum: the fields
+/// of a `for_type` rule take its strategy, the fields matching `default_fields` are reset, the
+/// other ones take the strategy of `all`, unless they are listed in `except`. The fields with a
+/// strategy of their own keep it.
+///
+/// A rule, a selector or an `except` which matches no field is reported, as it is likely a typo.
+fn apply_policies(attrs: &ContainerAttrs, data: &mut Data, errors: &mut Errors) {
+    if attrs.for_types.is_empty() && attrs.default_fields.is_empty() && attrs.all.is_none() {
+        return;
+    }
+    let fields: Vec<&mut Field> = match data {
+        Data::Struct(_, fields) => fields.iter_mut().collect(),
+        Data::Enum(variants) => variants
+            .iter_mut()
+            .flat_map(|variant| &mut variant.fields)
+            .collect(),
+    };
+    let mut typed = vec![false; attrs.for_types.len()];
+    let mut selected = vec![false; attrs.default_fields.len()];
+    let mut excepted = vec![false; attrs.except.len()];
+    for field in fields {
+        let name = match &field.member {
+            Member::Named(ident) => Some(ident.unraw().to_string()),
+            Member::Unnamed(_) => None,
+        };
+        let mut rule = None;
+        for (type_rule, typed) in attrs.for_types.iter().zip(&mut typed) {
+            if type_rule.matches(field.ty) {
+                *typed = true;
+                rule = rule.or(Some(type_rule));
+            }
+        }
+        let mut reset = false;
+        for (selector, selected) in attrs.default_fields.iter().zip(&mut selected) {
+            if name.as_deref().is_some_and(|name| selector.matches(name)) {
+                *selected = true;
+                reset = true;
+            }
+        }
+        let mut except = false;
+        for (ident, excepted) in attrs.except.iter().zip(&mut excepted) {
+            if name.as_deref() == Some(&*ident.unraw().to_string()) {
+                *excepted = true;
+                except = true;
+            }
+        }
+        if field.attrs.explicit {
+            continue;
+        }
+        match (rule, reset, attrs.all) {
+            (Some(rule), _, _) => field.attrs.strategy = rule.strategy.clone(),
+            (None, true, _) => field.attrs.strategy = Strategy::Default,
+            (None, false, Some(policy)) if !except => field.attrs.strategy = policy.strategy(),
+            _ => {}
+        }
+    }
+
+    for (rule, _) in attrs
+        .for_types
+        .iter()
+        .zip(typed)
+        .filter(|(_, typed)| !typed)
+    {
+        errors.push(syn::Error::new_spanned(
+            &rule.ty,
+            format!(
+                "no field has the type `{}`",
+                rule.ty.to_token_stream().to_string().replace(' ', "")
+            ),
+        ));
+    }
+    for (selector, _) in attrs
+        .default_fields
+        .iter()
+        .zip(selected)
+        .filter(|(_, selected)| !selected)
+    {
+        errors.push(syn::Error::new_spanned(
+            &selector.origin,
+            format!("`{}` matches no field", selector.pattern),
+        ));
+    }
+    for (ident, _) in attrs
+        .except
+        .iter()
+        .zip(excepted)
+        .filter(|(_, excepted)| !excepted)
+    {
+        errors.push(syn::Error::new_spanned(
+            ident,
+            format!("unknown field `{}`", ident),
+        ));
+    }
+}
+
+/// Report the `with_ctx` options used without a `#[clone(context = ...)]` on the container.
+fn check_without_context(data: &Data, errors: &mut Errors) {
+    let (variants, fields) = match data {
+        Data::Struct(_, fields) => (&[][..], &fields[..]),
+        Data::Enum(variants) => (&variants[..], &[][..]),
+    };
+    let attrs = variants
+        .iter()
+        .flat_map(|variant| {
+            std::iter::once(&variant.attrs).chain(variant.fields.iter().map(|field| &field.attrs))
+        })
+        .chain(fields.iter().map(|field| &field.attrs));
+    for attrs in attrs {
+        if let Strategy::WithCtx(func) = &attrs.strategy {
+            errors.push(syn::Error::new_spanned(
+                func,
+                "`with_ctx` requires a `#[clone(context = ...)]` on the struct or enum",
+            ));
+        }
+    }
+}
+
+/// Check the `to = Variant` remaps of the variants of an enum: the target must be another variant,
+/// whose fields of the same name (or position) have the same types as the ones moved across, a
+/// path matching a shorter one as in the `for_type` rules.
+fn check_remaps(variants: &[Variant], errors: &mut Errors) {
+    for variant in variants {
+        let profiles = variant
+            .attrs
+            .profiles
+            .iter()
+            .map(|profile| &profile.strategy);
+        for strategy in std::iter::once(&variant.attrs.strategy).chain(profiles) {
+            let Strategy::To(to) = strategy else {
+                continue;
+            };
+            let Some(target) = variants.iter().find(|other| other.ident == to) else {
+                errors.push(syn::Error::new_spanned(to, "unknown variant in `to`"));
+                continue;
+            };
+            if target.ident == variant.ident {
+                errors.push(syn::Error::new_spanned(
+                    to,
+                    "`to` must name another variant",
+                ));
+                continue;
+            }
+            if variant.style != target.style
+                && !variant.fields.is_empty()
+                && !target.fields.is_empty()
+            {
+                errors.push(syn::Error::new_spanned(
+                    to,
+                    format!(
+                        "`{}` and `{}` have different kinds of fields: `to` moves named fields to named fields, unnamed fields to unnamed fields",
+                        variant.ident, to
+                    ),
+                ));
+                continue;
+            }
+            for field in &target.fields {
+                let Some(moved) = variant
+                    .fields
+                    .iter()
+                    .find(|moved| moved.member == field.member)
+                else {
+                    continue;
+                };
+                if !same_type(moved.ty, field.ty) {
+                    errors.push(syn::Error::new_spanned(
+                        moved.ty,
+                        format!(
+                            "mismatched types in `to = {}`: field `{}` is a `{}` in `{}`",
+                            to,
+                            moved.member.to_token_stream(),
+                            field.ty.to_token_stream(),
+                            to
+                        ),
+                    ));
+                }
+            }
+        }
+    }
+}
+
+/// Check that a `#[clone(builder)]` is put on a struct with named fields, none of which reads the
+/// cloned value of another field: the builder clones each field on its own. The names of the
+/// setters and helpers generated for the fields must not be taken by a field or a profile.
+fn check_builder(builder: &syn::Path, data: &Data, errors: &mut Errors) {
+    let Data::Struct(Style::Named, fields) = data else {
+        errors.push(syn::Error::new_spanned(
+            builder,
+            "`builder` is only supported on structs with named fields",
+        ));
+        return;
+    };
+    for used in fields.iter().flat_map(|field| &field.attrs.uses) {
+        errors.push(syn::Error::new_spanned(
+            used,
+            "`uses` is not supported along with `builder`",
+        ));
+    }
+    // The setter of a field is named after it.
+    for field in fields {
+        if matches!(&field.member, Member::Named(ident) if ident.unraw() == "build") {
+            errors.push(syn::Error::new_spanned(
+                &field.member,
+                "a field named `build` is not supported along with `builder`: its setter would be \
+                 the `build` method",
+            ));
+        }
+    }
+    // The fields which are not set are cloned by the `clone_builder_<field>` helpers.
+    let helpers: Vec<_> = fields
+        .iter()
+        .filter_map(|field| match &field.member {
+            Member::Named(ident) => Some(format!("builder_{}", ident.unraw())),
+            Member::Unnamed(_) => None,
+        })
+        .collect();
+    for profile in fields.iter().flat_map(|field| &field.attrs.profiles) {
+        if helpers.contains(&profile.name.to_string()) {
+            let message = format!(
+                "the profile name `{0}` is reserved along with `builder`: `clone_{0}` exists",
+                profile.name
+            );
+            errors.push(syn::Error::new_spanned(&profile.name, message));
+        }
+    }
+}
+
+/// Check that a `#[clone(mask)]` is put on a struct with named fields, none of which reads the
+/// cloned value of another field, and that the `keep` options are only used along with it.
+fn check_mask(mask: Option<&syn::Path>, data: &Data, errors: &mut Errors) {
+    let fields = match (mask, data) {
+        (Some(_), Data::Struct(Style::Named, fields)) => fields,
+        (Some(mask), _) => {
+            errors.push(syn::Error::new_spanned(
+                mask,
+                "`mask` is only supported on structs with named fields",
+            ));
+            return;
+        }
+        (None, Data::Struct(_, fields)) => {
+            for keep in fields.iter().filter_map(|field| field.attrs.keep.as_ref()) {
+                errors.push(syn::Error::new_spanned(
+                    keep,
+                    "`keep` requires a `#[clone(mask)]` on the struct",
+                ));
+            }
+            return;
+        }
+        (None, Data::Enum(variants)) => {
+            let fields = variants.iter().flat_map(|variant| &variant.fields);
+            for keep in fields.filter_map(|field| field.attrs.keep.as_ref()) {
+                errors.push(syn::Error::new_spanned(
+                    keep,
+                    "`keep` is only supported on the fields of a struct",
+                ));
+            }
+            return;
+        }
+    };
+    for used in fields.iter().flat_map(|field| &field.attrs.uses) {
+        errors.push(syn::Error::new_spanned(
+            used,
+            "`uses` is not supported along with `mask`",
+        ));
+    }
+    for field in fields {
+        if let Strategy::Never(_) = field.attrs.strategy {
+            errors.push(syn::Error::new_spanned(
+                &field.member,
+                "`panic`, `unreachable` and `forbid` are not supported along with `mask`",
+            ));
+        }
+    }
+    let mut maskable = fields.iter().filter(|field| field.attrs.keep.is_none());
+    if let Some(field) = maskable.nth(MAX_MASKABLE_FIELDS) {
+        errors.push(syn::Error::new_spanned(
+            &field.member,
+            format!(
+                "`mask` supports at most {} fields: mark the others with `keep`",
+                MAX_MASKABLE_FIELDS
+            ),
+        ));
+    }
+}
+
+/// Check that the `exclude` options are only used along with a `#[clone(into = ...)]`, and that
+/// the values put in the projection are projections: the overrides of enum variants are values
+/// of the original type, and excluded fields cannot be read through `uses(...)`.
+fn check_into(into: Option<&Ident>, data: &Data, errors: &mut Errors) {
+    let (variants, fields) = match data {
+        Data::Struct(_, fields) => (&[][..], &fields[..]),
+        Data::Enum(variants) => (&variants[..], &[][..]),
+    };
+    let fields = variants
+        .iter()
+        .flat_map(|variant| &variant.fields)
+        .chain(fields);
+    let Some(into) = into else {
+        for exclude in fields.filter_map(|field| field.attrs.exclude.as_ref()) {
+            errors.push(syn::Error::new_spanned(
+                exclude,
+                "`exclude` requires a `#[clone(into = ...)]` on the struct or enum",
+            ));
+        }
+        return;
+    };
+    for variant in variants {
+        let allowed = matches!(variant.attrs.strategy, Strategy::Reset | Strategy::Never(_));
+        if !variant.attrs.is_standard() && !allowed {
+            errors.push(syn::Error::new_spanned(
+                variant.ident,
+                format!(
+                    "the override of a variant is not a `{}`: only the fields of a variant can be overridden along with `into`",
+                    into
+                ),
+            ));
+        }
+    }
+    if let Data::Struct(_, fields) = data {
+        for field in fields {
+            for used in &field.attrs.uses {
+                let excluded = fields.iter().any(|other| {
+                    other.attrs.exclude.is_some()
+                        && matches!(&other.member, Member::Named(ident) if ident == used)
+                });
+                if excluded {
+                    errors.push(syn::Error::new_spanned(
+                        used,
+                        "`uses` cannot read an excluded field",
+                    ));
+                }
+            }
+        }
+    }
+}
+
+/// The number of bits of the `<Name>FieldSet` generated by `#[clone(mask)]`.
+pub(crate) const MAX_MASKABLE_FIELDS: usize = 64;
+
+/// Check the `uses(...)` options of the fields of a struct: fields are cloned in declaration
+/// order, so a field can only read the cloned value of a named field declared before it.
+fn check_uses(style: Style, fields: &[Field], errors: &mut Errors) {
+    for (position, field) in fields.iter().enumerate() {
+        for used in &field.attrs.uses {
+            let declared = fields
+                .iter()
+                .position(|other| matches!(&other.member, Member::Named(ident) if ident == used));
+            let message = match declared {
+                _ if style != Style::Named => "`uses` requires a struct with named fields",
+                None => "unknown field in `uses`",
+                Some(declared) if declared >= position => {
+                    "`uses` can only read fields declared before this one: fields are cloned in declaration order"
+                }
+                Some(_) => continue,
+            };
+            errors.push(syn::Error::new_spanned(used, message));
+        }
+    }
+}
@@ -0,0 +1,1008 @@
+use proc_macro2::{Delimiter, Ident, Spacing, Span, TokenStream, TokenTree};
+use quote::ToTokens;
+use syn::parse::{Parse, ParseStream};
+use syn::punctuated::Punctuated;
+use syn::{
+    Attribute, Expr, GenericArgument, LitStr, Member, Meta, MetaList, Path, PathArguments, Token,
+    Type, WherePredicate,
+};
+
+use crate::internals::Method;
+
+/// Collects the errors found while reading the input, to report them all at once, along with the
+/// uses of a deprecated syntax, which are only warned about.
+#[derive(Default)]
+pub(crate) struct Errors {
+    errors: Option<syn::Error>,
+    deprecated: Vec<Span>,
+}
+
+impl Errors {
+    /// Add an error to the report.
+    pub fn push(&mut self, error: syn::Error) {
+        match &mut self.errors {
+            Some(errors) => errors.combine(error),
+            None => self.errors = Some(error),
+        }
+    }
+
+    /// Add a use of the deprecated `#[clone(expr)]` syntax.
+    pub fn deprecate(&mut self, span: Span) {
+        self.deprecated.push(span);
+    }
+
+    /// Get the combined errors, if any, or the spans of the deprecated syntax to warn about.
+    pub fn finish(self) -> syn::Result<Vec<Span>> {
+        match self.errors {
+            Some(errors) => Err(errors),
+            None => Ok(self.deprecated),
+        }
+    }
+}
+
+/// Defines how the value of a field (or of an enum variant) is computed on clone.
+#[derive(Clone)]
+pub(crate) enum Strategy {
+    /// `#[clone]` or no attribute: clone as usual.
+    Standard,
+    /// `#[clone = value]` or `#[clone(value)]`: the value is overridden by an expression.
+    Value(Expr),
+    /// `#[clone(default)]`: the value is reset to `Default::default()`.
+    Default,
+    /// `#[clone(with = func)]` or `#[clone(clone_with = "func")]`: the value is computed by a
+    /// function (a path or a closure).
+    CloneWith(Expr),
+    /// `#[clone(with_parent = func)]`: the value is computed by a function which also receives
+    /// the whole original value, called as `func(&self.field, self)`.
+    WithParent(Expr),
+    /// `#[clone(with_ctx = func)]`: in `clone_with_ctx`, the value is computed by a function which
+    /// also receives the context, called as `func(&self.field, ctx)`. Cloned as usual by `Clone`.
+    WithCtx(Expr),
+    /// `#[clone(try_with = func)]`: in `try_clone`, the value is computed by a fallible function,
+    /// called as `func(&self.field)?`. Cloned as usual by `Clone`.
+    TryWith(Expr),
+    /// `#[clone(deep)]`: the `Rc`, `Arc` and `Box` pointers of the value are allocated anew
+    /// around a clone of their pointee, instead of being shared.
+    Deep,
+    /// `#[clone(set(a.b = value, ...))]`: the value is cloned as usual, then the nested places are
+    /// assigned.
+    Set(Vec<Assignment>),
+    /// `#[clone(to = Variant)]`: the enum variant is remapped to another variant of the enum, the
+    /// fields of the same name (or position) being moved across and the others defaulted.
+    To(Ident),
+    /// `#[clone(reset)]`: the enum variant is kept, each of its fields being reset to its override
+    /// or to `Default::default()`.
+    Reset,
+    /// `#[clone(with_fields = func)]`: the enum variant is computed by a function receiving its
+    /// fields by reference, in declaration order, called as `func(x, y)`.
+    WithFields(Expr),
+    /// `#[clone(panic = "...")]`, `#[clone(unreachable)]` or `#[clone(forbid)]`: the field (or
+    /// the variant) must never be cloned.
+    Never(Never),
+}
+
+/// A nested place of a field assigned by `#[clone(set(...))]`: `session.token = None`.
+#[derive(Clone)]
+pub(crate) struct Assignment {
+    pub place: Punctuated<Member, Token![.]>,
+    pub value: Expr,
+}
+
+/// How a field (or a variant) which must never be cloned fails to be cloned.
+#[derive(Clone)]
+pub(crate) enum Never {
+    /// `#[clone(panic = "reason")]`: panic with the reason.
+    Panic(LitStr),
+    /// `#[clone(unreachable)]`: the value is not expected to be cloned at all.
+    Unreachable,
+    /// `#[clone(forbid)]`: `try_clone` returns an error, the other methods panic.
+    Forbid,
+}
+
+/// The strategy of the fields selected by a policy of the struct or enum: `#[clone(all = default)]`.
+#[derive(Clone, Copy)]
+pub(crate) enum Policy {
+    /// `default`: the fields are reset to `Default::default()`.
+    Default,
+    /// `deep`: the fields are deeply cloned.
+    Deep,
+}
+
+impl Policy {
+    /// The strategy given to the selected fields.
+    pub fn strategy(self) -> Strategy {
+        match self {
+            Policy::Default => Strategy::Default,
+            Policy::Deep => Strategy::Deep,
+        }
+    }
+}
+
+/// A field name pattern of `#[clone(default_fields = "...")]`, where `*` matches any characters:
+/// `cache_*`, `*_scratch`.
+pub(crate) struct Selector {
+    pub pattern: String,
+    /// The option holding the pattern, to report errors at it.
+    pub origin: LitStr,
+}
+
+impl Selector {
+    /// Checks if the name of a field matches the pattern.
+    pub fn matches(&self, name: &str) -> bool {
+        let mut parts = self.pattern.split('*');
+        let first = parts.next().unwrap_or_default();
+        let Some(mut rest) = name.strip_prefix(first) else {
+            return false;
+        };
+        let parts: Vec<_> = parts.collect();
+        let Some((last, middle)) = parts.split_last() else {
+            return rest.is_empty();
+        };
+        for part in middle {
+            match rest.find(part) {
+                Some(index) => rest = &rest[index + part.len()..],
+                None => return false,
+            }
+        }
+        rest.ends_with(last)
+    }
+}
+
+/// A `#[clone(for_type(Type = ...))]` rule: the strategy of the fields of a type.
+pub(crate) struct TypeRule {
+    pub ty: Type,
+    pub strategy: Strategy,
+}
+
+impl TypeRule {
+    /// Checks if a field of type `ty` is selected by the rule. The types are compared as written,
+    /// except that a path also matches a shorter one naming the same type, down to its generic
+    /// arguments: a rule for `Option<std::time::Instant>` selects the fields of type
+    /// `Option<Instant>` or `Option<time::Instant>`.
+    pub fn matches(&self, ty: &Type) -> bool {
+        same_type(&self.ty, ty)
+    }
+}
+
+/// Compares two types as written, letting a path match a shorter one (see [`TypeRule::matches`]).
+pub(crate) fn same_type(a: &Type, b: &Type) -> bool {
+    match (a, b) {
+        (Type::Path(a), Type::Path(b)) if a.qself.is_none() && b.qself.is_none() => {
+            same_path(&a.path, &b.path)
+        }
+        _ => same_tokens(a, b),
+    }
+}
+
+fn same_path(a: &Path, b: &Path) -> bool {
+    let (long, short) = match a.segments.len() >= b.segments.len() {
+        true => (a, b),
+        false => (b, a),
+    };
+    let skipped = long.segments.len() - short.segments.len();
+    (short.leading_colon.is_none() || skipped == 0)
+        && long
+            .segments
+            .iter()
+            .skip(skipped)
+            .zip(&short.segments)
+            .all(|(a, b)| a.ident == b.ident && same_arguments(&a.arguments, &b.arguments))
+}
+
+fn same_arguments(a: &PathArguments, b: &PathArguments) -> bool {
+    let (PathArguments::AngleBracketed(a), PathArguments::AngleBracketed(b)) = (a, b) else {
+        return same_tokens(a, b);
+    };
+    a.args.len() == b.args.len()
+        && a.args.iter().zip(&b.args).all(|pair| match pair {
+            (GenericArgument::Type(a), GenericArgument::Type(b)) => same_type(a, b),
+            (a, b) => same_tokens(a, b),
+        })
+}
+
+fn same_tokens(a: &impl ToTokens, b: &impl ToTokens) -> bool {
+    a.to_token_stream().to_string() == b.to_token_stream().to_string()
+}
+
+/// The item a `#[clone...]` attribute is put on, which defines the options it accepts.
+#[derive(Clone, Copy, PartialEq)]
+pub(crate) enum Target {
+    /// A field of a struct or of an enum variant.
+    Field,
+    /// An enum variant.
+    Variant,
+}
+
+/// Options of a field (or of an enum variant), read from its `#[clone...]` attributes.
+pub(crate) struct FieldAttrs {
+    pub strategy: Strategy,
+    pub bound: Option<Punctuated<WherePredicate, Token![,]>>,
+    /// `#[clone(uses(a, b))]`: sibling fields whose cloned values are read by the override.
+    pub uses: Vec<Ident>,
+    /// `#[clone(profile = "name", ...)]`: the strategies of the `clone_<name>` methods.
+    pub profiles: Vec<Profile>,
+    /// `#[clone(keep)]`: the field is never reset by `clone_except` and `clone_only`. The option
+    /// is kept to report errors at it.
+    pub keep: Option<Path>,
+    /// `#[clone(exclude)]`: the field is left out of the `#[clone(into = ...)]` projection. The
+    /// option is kept to report errors at it.
+    pub exclude: Option<Path>,
+    /// `#[clone(map = func)]`: a function applied to the cloned value, whatever its strategy.
+    pub map: Option<Expr>,
+    /// Whether the strategy is set by the attributes of the field, a bare `#[clone]` included:
+    /// the policies of the struct or enum do not apply then.
+    pub explicit: bool,
+}
+
+/// The strategy of a field (or of an enum variant) in a `clone_<name>` method.
+pub(crate) struct Profile {
+    pub name: Ident,
+    pub strategy: Strategy,
+}
+
+/// Options of the struct or enum itself, read from its `#[clone(...)]` attributes.
+#[derive(Default)]
+pub(crate) struct ContainerAttrs {
+    pub bound: Option<Punctuated<WherePredicate, Token![,]>>,
+    /// `#[clone(context = Type)]`: generate `clone_with_ctx(&self, ctx: &mut Type)`.
+    pub context: Option<Type>,
+    /// `#[clone(skip_clone)]`: do not implement `Clone`, only `clone_with_ctx`.
+    pub skip_clone: bool,
+    /// `#[clone(error = Type)]`: the error type of `try_clone`.
+    pub error: Option<Type>,
+    /// `#[clone(builder)]`: generate a `clone_builder()` returning a `<Name>CloneBuilder`. The
+    /// option is kept to report errors at it.
+    pub builder: Option<Path>,
+    /// `#[clone(mask)]`: generate `clone_except` and `clone_only`, along with the `<Name>Field`
+    /// and `<Name>FieldSet` types. The option is kept to report errors at it.
+    pub mask: Option<Path>,
+    /// `#[clone(into = Name)]`: generate a `Name` type holding the fields which are not excluded,
+    /// along with `impl From<&Self> for Name`.
+    pub into: Option<Ident>,
+    /// `#[clone(derive(...))]`: the derives of the `into` type.
+    pub derive: Vec<Path>,
+    /// `#[clone(default_fields = "cache_*, *_scratch")]`: the fields reset to `Default::default()`
+    /// when they have no strategy of their own, selected by name.
+    pub default_fields: Vec<Selector>,
+    /// `#[clone(all = default)]`: the strategy of all the fields which have none of their own.
+    pub all: Option<Policy>,
+    /// `#[clone(except(id, name))]`: the fields left out of `all`.
+    pub except: Vec<Ident>,
+    /// `#[clone(for_type(Type = ...))]`: the strategies of the fields of a type, when they have
+    /// none of their own.
+    pub for_types: Vec<TypeRule>,
+}
+
+/// Options that can be given in a `#[clone(...)]` list on a field.
+const FIELD_OPTIONS: &[&str] = &[
+    "value",
+    "default",
+    "with",
+    "clone_with",
+    "with_parent",
+    "with_ctx",
+    "try_with",
+    "deep",
+    "set",
+    "panic",
+    "unreachable",
+    "forbid",
+    "map",
+    "uses",
+    "keep",
+    "exclude",
+    "bound",
+    "profile",
+];
+
+/// Options that can be given in a `#[clone(...)]` list on an enum variant.
+const VARIANT_OPTIONS: &[&str] = &[
+    "value",
+    "default",
+    "with",
+    "clone_with",
+    "with_fields",
+    "with_ctx",
+    "try_with",
+    "to",
+    "reset",
+    "panic",
+    "unreachable",
+    "forbid",
+    "map",
+    "bound",
+    "profile",
+];
+
+impl Target {
+    /// Options accepted in a `#[clone(...)]` list on this item.
+    fn options(self) -> &'static [&'static str] {
+        match self {
+            Target::Field => FIELD_OPTIONS,
+            Target::Variant => VARIANT_OPTIONS,
+        }
+    }
+}
+
+/// Options that can be given in a `#[clone(...)]` list on the struct or enum.
+const CONTAINER_OPTIONS: &[&str] = &[
+    "bound",
+    "context",
+    "skip_clone",
+    "error",
+    "builder",
+    "mask",
+    "into",
+    "derive",
+    "default_fields",
+    "all",
+    "except",
+    "for_type",
+];
+
+impl FieldAttrs {
+    /// Read the `#[clone...]` attributes of a field or an enum variant.
+    ///
+    /// Options spread over several attributes are merged: `#[clone(default)] #[clone(bound = "")]`
+    /// is the same as `#[clone(default, bound = "")]`.
+    pub fn from_ast(attrs: &[Attribute], target: Target, errors: &mut Errors) -> Self {
+        let mut slots = FieldSlots {
+            strategy: Attr::none(),
+            bound: Attr::none(),
+            uses: Attr::none(),
+            profiles: Vec::new(),
+            keep: Attr::none(),
+            exclude: Attr::none(),
+            map: Attr::none(),
+            deprecated: Vec::new(),
+        };
+
+        // Check for the `#[clone...]` attributes
+        let mut bare = false;
+        for attr in attrs.iter().filter(|attr| attr.path().is_ident("clone")) {
+            let parsed = match &attr.meta {
+                // Handle `#[clone]` by cloning as usual
+                Meta::Path(_) => {
+                    bare = true;
+                    Ok(())
+                }
+                // Handle #[clone = value].
+                Meta::NameValue(item) => {
+                    let value = Strategy::Value(item.value.clone());
+                    slots.strategy.set(attr, "value", value)
+                }
+                // Handle `#[clone(item1, item2)]` as `#[clone(items)]`.
+                Meta::List(items) => parse_field_list(items, target, &mut slots),
+            };
+            if let Err(error) = parsed {
+                errors.push(error);
+            }
+        }
+
+        for span in slots.deprecated {
+            errors.deprecate(span);
+        }
+        let explicit = bare || slots.strategy.get_ref().is_some();
+        FieldAttrs {
+            strategy: slots.strategy.get().unwrap_or(Strategy::Standard),
+            bound: slots.bound.get(),
+            uses: slots.uses.get().unwrap_or_default(),
+            profiles: slots
+                .profiles
+                .into_iter()
+                .map(|(name, strategy)| Profile {
+                    name,
+                    strategy: strategy.get().unwrap_or(Strategy::Standard),
+                })
+                .collect(),
+            keep: slots.keep.get(),
+            exclude: slots.exclude.get(),
+            map: slots.map.get(),
+            explicit,
+        }
+    }
+
+    /// Checks if the field (or variant) is cloned as usual by `Clone`, without being mapped.
+    pub fn is_standard(&self) -> bool {
+        self.map.is_none()
+            && matches!(
+                self.strategy,
+                Strategy::Standard | Strategy::WithCtx(_) | Strategy::TryWith(_)
+            )
+    }
+
+    /// The strategy of the field (or variant) in the generated method: the one of the profile
+    /// for a `clone_<profile>` method, if set, otherwise the base one.
+    pub fn strategy_for(&self, method: Method) -> &Strategy {
+        if let Method::Profile(name) = method {
+            if let Some(profile) = self.profiles.iter().find(|profile| profile.name == *name) {
+                return &profile.strategy;
+            }
+        }
+        &self.strategy
+    }
+}
+
+/// The options of a field (or of an enum variant) being read from its attributes.
+struct FieldSlots {
+    strategy: Attr<Strategy>,
+    bound: Attr<Punctuated<WherePredicate, Token![,]>>,
+    uses: Attr<Vec<Ident>>,
+    profiles: Vec<(Ident, Attr<Strategy>)>,
+    keep: Attr<Path>,
+    exclude: Attr<Path>,
+    map: Attr<Expr>,
+    /// The deprecated `#[clone(expr)]` attributes.
+    deprecated: Vec<Span>,
+}
+
+/// Read a `#[clone(...)]` list of a field or an enum variant: either options or an expression
+/// overriding the value.
+///
+/// A list starting with `profile = "name"` sets the strategy of this profile only.
+fn parse_field_list(items: &MetaList, target: Target, slots: &mut FieldSlots) -> syn::Result<()> {
+    let options = target.options();
+    // Deprecated `#[clone(expr)]`, superseded by `#[clone(value = expr)]`.
+    if is_expression(&items.tokens, options) {
+        let value = Strategy::Value(items.parse_args()?);
+        slots.deprecated.push(items.delimiter.span().join());
+        return slots.strategy.set(items, "value", value);
+    }
+
+    let mut first = true;
+    let mut profile = None;
+    items.parse_nested_meta(|meta| {
+        let path = &meta.path;
+        let is_first = std::mem::replace(&mut first, false);
+        if !options.iter().any(|option| path.is_ident(option)) {
+            return Err(unknown_option(path, options));
+        }
+        // `#[clone(profile = "name", ...)]`
+        if path.is_ident("profile") {
+            if !is_first {
+                return Err(syn::Error::new_spanned(
+                    path,
+                    "`profile` must be the first option of a `#[clone(...)]` list",
+                ));
+            }
+            let lit: LitStr = meta.value()?.parse()?;
+            let name: Ident = lit.parse().map_err(|_| {
+                syn::Error::new_spanned(&lit, "expected a profile name usable in `clone_<name>`")
+            })?;
+            if RESERVED_PROFILES.iter().any(|reserved| name == reserved) {
+                let message = format!(
+                    "the profile name `{0}` is reserved: `clone_{0}` exists",
+                    name
+                );
+                return Err(syn::Error::new_spanned(&lit, message));
+            }
+            let index = match slots.profiles.iter().position(|(other, _)| *other == name) {
+                Some(index) => index,
+                None => {
+                    slots.profiles.push((name, Attr::none()));
+                    slots.profiles.len() - 1
+                }
+            };
+            profile = Some(index);
+            return Ok(());
+        }
+
+        let strategy = match profile {
+            Some(index) => &mut slots.profiles[index].1,
+            None => &mut slots.strategy,
+        };
+        // `#[clone(value = expr)]`
+        if path.is_ident("value") {
+            return strategy.set(path, "value", Strategy::Value(meta.value()?.parse()?));
+        }
+        // `#[clone(default)]` => clone with default value
+        if path.is_ident("default") {
+            return strategy.set(path, "default", Strategy::Default);
+        }
+        // `#[clone(with = func)]`, or `#[clone(clone_with = "func")]`
+        if path.is_ident("with") {
+            let func = parse_function(meta.value()?)?;
+            return strategy.set(path, "with", Strategy::CloneWith(func));
+        }
+        if path.is_ident("clone_with") {
+            let func = parse_function(meta.value()?)?;
+            return strategy.set(path, "clone_with", Strategy::CloneWith(func));
+        }
+        // `#[clone(with_fields = func)]`
+        if path.is_ident("with_fields") {
+            let func = parse_function(meta.value()?)?;
+            return strategy.set(path, "with_fields", Strategy::WithFields(func));
+        }
+        // `#[clone(with_parent = func)]`
+        if path.is_ident("with_parent") {
+            let func = parse_function(meta.value()?)?;
+            return strategy.set(path, "with_parent", Strategy::WithParent(func));
+        }
+        // `#[clone(with_ctx = func)]`
+        if path.is_ident("with_ctx") {
+            let func = parse_function(meta.value()?)?;
+            return strategy.set(path, "with_ctx", Strategy::WithCtx(func));
+        }
+        // `#[clone(try_with = func)]`
+        if path.is_ident("try_with") {
+            let func = parse_function(meta.value()?)?;
+            return strategy.set(path, "try_with", Strategy::TryWith(func));
+        }
+        // `#[clone(deep)]`
+        if path.is_ident("deep") {
+            return strategy.set(path, "deep", Strategy::Deep);
+        }
+        // `#[clone(set(a.b = value, ...))]`
+        if path.is_ident("set") {
+            let content;
+            syn::parenthesized!(content in meta.input);
+            let assignments = Punctuated::<Assignment, Token![,]>::parse_terminated(&content)?;
+            if assignments.is_empty() {
+                return Err(syn::Error::new_spanned(
+                    path,
+                    "expected at least one `field.path = value` in `set(...)`",
+                ));
+            }
+            return strategy.set(
+                path,
+                "set",
+                Strategy::Set(assignments.into_iter().collect()),
+            );
+        }
+        // `#[clone(panic = "reason")]`, `#[clone(unreachable)]`, `#[clone(forbid)]`
+        if path.is_ident("panic") {
+            let reason = Never::Panic(meta.value()?.parse()?);
+            return strategy.set(path, "panic", Strategy::Never(reason));
+        }
+        if path.is_ident("unreachable") {
+            return strategy.set(path, "unreachable", Strategy::Never(Never::Unreachable));
+        }
+        if path.is_ident("forbid") {
+            return strategy.set(path, "forbid", Strategy::Never(Never::Forbid));
+        }
+        // `#[clone(to = Variant)]`
+        if path.is_ident("to") {
+            return strategy.set(path, "to", Strategy::To(meta.value()?.parse()?));
+        }
+        // `#[clone(reset)]`
+        if path.is_ident("reset") {
+            return strategy.set(path, "reset", Strategy::Reset);
+        }
+
+        // The other options apply to all the profiles.
+        if profile.is_some() {
+            let message = format!(
+                "`{}` cannot be set for a profile only",
+                path.to_token_stream()
+            );
+            return Err(syn::Error::new_spanned(path, message));
+        }
+        // `#[clone(uses(a, b))]`
+        if path.is_ident("uses") {
+            let content;
+            syn::parenthesized!(content in meta.input);
+            let fields = Punctuated::<Ident, Token![,]>::parse_terminated(&content)?;
+            return slots.uses.set(path, "uses", fields.into_iter().collect());
+        }
+        // `#[clone(keep)]`
+        if path.is_ident("keep") {
+            return slots.keep.set(path, "keep", path.clone());
+        }
+        // `#[clone(exclude)]`
+        if path.is_ident("exclude") {
+            return slots.exclude.set(path, "exclude", path.clone());
+        }
+        // `#[clone(map = func)]`
+        if path.is_ident("map") {
+            return slots.map.set(path, "map", parse_function(meta.value()?)?);
+        }
+        // `#[clone(bound = "...")]`
+        if path.is_ident("bound") {
+            let bound = parse_bound(&meta.value()?.parse()?)?;
+            return slots.bound.set(path, "bound", bound);
+        }
+        Err(unknown_option(path, options))
+    })?;
+
+    // `#[clone(profile = "name")]` alone clones as usual in this profile.
+    if let Some(index) = profile {
+        let (name, strategy) = &mut slots.profiles[index];
+        if strategy.get_ref().is_none() {
+            strategy.set(name.clone(), "profile", Strategy::Standard)?;
+        }
+    }
+    Ok(())
+}
+
+impl ContainerAttrs {
+    /// Read the `#[clone(...)]` attributes of the struct or enum.
+    pub fn from_ast(attrs: &[Attribute], errors: &mut Errors) -> Self {
+        let mut bound = Attr::none();
+        let mut context = Attr::none();
+        let mut skip_clone = Attr::none();
+        let mut error_type = Attr::none();
+        let mut builder = Attr::none();
+        let mut mask = Attr::none();
+        let mut into = Attr::none();
+        let mut derive = Attr::none();
+        let mut default_fields = Attr::none();
+        let mut all = Attr::none();
+        let mut except = Attr::none();
+        let mut for_types: Vec<TypeRule> = Vec::new();
+        for attr in attrs.iter().filter(|attr| attr.path().is_ident("clone")) {
+            let Meta::List(items) = &attr.meta else {
+                errors.push(syn::Error::new_spanned(
+                    attr,
+                    "expected `#[clone(...)]` options on a struct or an enum",
+                ));
+                continue;
+            };
+            let parsed = items.parse_nested_meta(|meta| {
+                let path = &meta.path;
+                // `#[clone(bound = "...")]`
+                if path.is_ident("bound") {
+                    return bound.set(path, "bound", parse_bound(&meta.value()?.parse()?)?);
+                }
+                // `#[clone(context = Type)]`
+                if path.is_ident("context") {
+                    return context.set(path, "context", meta.value()?.parse()?);
+                }
+                // `#[clone(skip_clone)]`
+                if path.is_ident("skip_clone") {
+                    return skip_clone.set(path, "skip_clone", path.clone());
+                }
+                // `#[clone(error = Type)]`
+                if path.is_ident("error") {
+                    return error_type.set(path, "error", meta.value()?.parse()?);
+                }
+                // `#[clone(builder)]`
+                if path.is_ident("builder") {
+                    return builder.set(path, "builder", path.clone());
+                }
+                // `#[clone(mask)]`
+                if path.is_ident("mask") {
+                    return mask.set(path, "mask", path.clone());
+                }
+                // `#[clone(into = Name)]`
+                if path.is_ident("into") {
+                    return into.set(path, "into", meta.value()?.parse()?);
+                }
+                // `#[clone(derive(Debug, ...))]`
+                if path.is_ident("derive") {
+                    let content;
+                    syn::parenthesized!(content in meta.input);
+                    let derives = Punctuated::<Path, Token![,]>::parse_terminated(&content)?;
+                    return derive.set(path, "derive", (path.clone(), derives));
+                }
+                // `#[clone(default_fields = "cache_*, *_scratch")]`
+                if path.is_ident("default_fields") {
+                    let selectors = parse_selectors(&meta.value()?.parse()?)?;
+                    return default_fields.set(path, "default_fields", selectors);
+                }
+                // `#[clone(all = default)]`
+                if path.is_ident("all") {
+                    let policy: Ident = meta.value()?.parse()?;
+                    let policy = match policy.to_string().as_str() {
+                        "default" => Policy::Default,
+                        "deep" => Policy::Deep,
+                        _ => {
+                            return Err(syn::Error::new_spanned(
+                                policy,
+                                "expected `all = default` or `all = deep`",
+                            ))
+                        }
+                    };
+                    return all.set(path, "all", policy);
+                }
+                // `#[clone(except(id, name))]`
+                if path.is_ident("except") {
+                    let content;
+                    syn::parenthesized!(content in meta.input);
+                    let fields = Punctuated::<Ident, Token![,]>::parse_terminated(&content)?;
+                    return except.set(path, "except", (path.clone(), fields));
+                }
+                // `#[clone(for_type(Type = "expr"))]`, `#[clone(for_type(Type = default))]`
+                if path.is_ident("for_type") {
+                    let content;
+                    syn::parenthesized!(content in meta.input);
+                    let rule = parse_type_rule(&content)?;
+                    let ty = rule.ty.to_token_stream().to_string();
+                    if for_types
+                        .iter()
+                        .any(|other| other.ty.to_token_stream().to_string() == ty)
+                    {
+                        let message =
+                            format!("duplicate `for_type` rule for `{}`", ty.replace(' ', ""));
+                        return Err(syn::Error::new_spanned(&rule.ty, message));
+                    }
+                    for_types.push(rule);
+                    return Ok(());
+                }
+                Err(unknown_option(path, CONTAINER_OPTIONS))
+            });
+            if let Err(error) = parsed {
+                errors.push(error);
+            }
+        }
+
+        let context = context.get();
+        let skip_clone = skip_clone.get();
+        if let (None, Some(path)) = (&context, &skip_clone) {
+            errors.push(syn::Error::new_spanned(
+                path,
+                "`skip_clone` requires a `context`: nothing would be generated",
+            ));
+        }
+        let into = into.get();
+        let derive = derive.get();
+        if let (None, Some((path, _))) = (&into, &derive) {
+            errors.push(syn::Error::new_spanned(
+                path,
+                "`derive` requires an `into`: it applies to the generated type",
+            ));
+        }
+        let all = all.get();
+        let except = except.get();
+        if let (None, Some((path, _))) = (&all, &except) {
+            errors.push(syn::Error::new_spanned(
+                path,
+                "`except` requires an `all`: it lists the fields left out of it",
+            ));
+        }
+        ContainerAttrs {
+            bound: bound.get(),
+            context,
+            skip_clone: skip_clone.is_some(),
+            error: error_type.get(),
+            builder: builder.get(),
+            mask: mask.get(),
+            into,
+            derive: derive
+                .map(|(_, derives)| derives.into_iter().collect())
+                .unwrap_or_default(),
+            default_fields: default_fields.get().unwrap_or_default(),
+            all,
+            except: except
+                .map(|(_, fields)| fields.into_iter().collect())
+                .unwrap_or_default(),
+            for_types,
+        }
+    }
+}
+
+/// An option read from the `#[clone...]` attributes, along with the tokens that set it.
+///
+/// Several options can share the same slot when they are mutually exclusive (`default` and
+/// `clone_with` both set the strategy): setting it twice is reported at both places.
+struct Attr<T> {
+    value: Option<(TokenStream, &'static str, T)>,
+}
+
+impl<T> Attr<T> {
+    fn none() -> Self {
+        Attr { value: None }
+    }
+
+    /// Set the option, unless it has already been set by the same or by a conflicting option.
+    fn set(&mut self, origin: impl ToTokens, name: &'static str, value: T) -> syn::Result<()> {
+        if let Some((first_origin, first_name, _)) = &self.value {
+            let message = match *first_name == name {
+                true => format!("duplicate clone option `{}`", name),
+                false => format!("conflicting clone options `{}` and `{}`", first_name, name),
+            };
+            let mut error = syn::Error::new_spanned(origin, message);
+            error.combine(syn::Error::new_spanned(
+                first_origin,
+                format!("`{}` is first set here", first_name),
+            ));
+            return Err(error);
+        }
+        self.value = Some((origin.into_token_stream(), name, value));
+        Ok(())
+    }
+
+    fn get(self) -> Option<T> {
+        self.value.map(|(_, _, value)| value)
+    }
+
+    fn get_ref(&self) -> Option<&T> {
+        self.value.as_ref().map(|(_, _, value)| value)
+    }
+}
+
+/// Parse the function of a `with = ...` option: a path (`Self::reset`, `reset::<u32>`), a closure,
+/// or a string holding a path (`"Self::reset"`) as in the former `clone_with = "..."` syntax.
+fn parse_function(input: ParseStream) -> syn::Result<Expr> {
+    if input.peek(LitStr) {
+        let func: LitStr = input.parse()?;
+        return Ok(Expr::Path(func.parse()?));
+    }
+    match input.parse()? {
+        func @ (Expr::Path(_) | Expr::Closure(_)) => Ok(func),
+        other => Err(syn::Error::new_spanned(
+            other,
+            "expected a function path or a closure",
+        )),
+    }
+}
+
+impl Parse for Assignment {
+    /// Parse `a.b = value`.
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let place = Punctuated::parse_separated_nonempty(input)?;
+        input.parse::<Token![=]>()?;
+        Ok(Assignment {
+            place,
+            value: input.parse()?,
+        })
+    }
+}
+
+/// Parse the content of a `for_type(...)` option: `Type = default`, `Type = deep`, or
+/// `Type = "expr"` overriding the value of the fields with an expression.
+fn parse_type_rule(input: ParseStream) -> syn::Result<TypeRule> {
+    let ty: Type = input.parse()?;
+    input.parse::<Token![=]>()?;
+    let strategy = match input.parse::<Option<LitStr>>()? {
+        Some(value) => Strategy::Value(value.parse()?),
+        None => {
+            let policy: Ident = input.parse()?;
+            match policy.to_string().as_str() {
+                "default" => Strategy::Default,
+                "deep" => Strategy::Deep,
+                _ => {
+                    return Err(syn::Error::new_spanned(
+                        policy,
+                        "expected `default`, `deep` or an expression as a string",
+                    ))
+                }
+            }
+        }
+    };
+    Ok(TypeRule { ty, strategy })
+}
+
+/// Parse the comma-separated field name patterns of a `default_fields = "..."` option.
+fn parse_selectors(patterns: &LitStr) -> syn::Result<Vec<Selector>> {
+    patterns
+        .value()
+        .split(',')
+        .map(|pattern| {
+            let pattern = pattern.trim();
+            let valid = !pattern.is_empty()
+                && pattern
+                    .chars()
+                    .all(|c| c.is_alphanumeric() || c == '_' || c == '*');
+            if !valid {
+                let message = format!(
+                    "invalid field selector `{}`: expected a field name, where `*` matches any characters",
+                    pattern
+                );
+                return Err(syn::Error::new_spanned(patterns, message));
+            }
+            Ok(Selector {
+                pattern: pattern.to_string(),
+                origin: patterns.clone(),
+            })
+        })
+        .collect()
+}
+
+/// Parse the where predicates of a `bound = "..."` option.
+fn parse_bound(predicates: &LitStr) -> syn::Result<Punctuated<WherePredicate, Token![,]>> {
+    predicates.parse_with(Punctuated::parse_terminated)
+}
+
+/// Profile names whose `clone_<name>` method is generated otherwise: `clone_from` by `Clone`, the
+/// others by the derives and container options.
+const RESERVED_PROFILES: &[&str] = &[
+    "from",
+    "with_ctx",
+    "graph",
+    "graph_in",
+    "except",
+    "only",
+    "builder",
+    "into_projection",
+];
+
+/// Options holding a parenthesized list: `#[clone(uses(a, b))]`, `#[clone(set(a.b = value))]`.
+const LIST_OPTIONS: &[&str] = &["uses", "set"];
+
+/// Checks if a `#[clone(...)]` list holds an expression rather than options.
+///
+/// Options are either `key = ...` pairs, lists (`uses(a, b)`) or flags: a lone identifier which is
+/// a known option, or close enough to one to be reported as a typo (`#[clone(defualt)]`) rather
+/// than being read as an expression.
+fn is_expression(tokens: &TokenStream, options: &[&'static str]) -> bool {
+    let mut tokens = tokens.clone().into_iter();
+    match (tokens.next(), tokens.next()) {
+        (Some(TokenTree::Ident(ident)), None) => !is_option_like(&ident, options),
+        (Some(TokenTree::Ident(ident)), Some(TokenTree::Group(group)))
+            if group.delimiter() == Delimiter::Parenthesis
+                && LIST_OPTIONS.iter().any(|option| ident == option) =>
+        {
+            match tokens.next() {
+                None => false,
+                Some(TokenTree::Punct(punct)) => punct.as_char() != ',',
+                Some(_) => true,
+            }
+        }
+        (Some(TokenTree::Ident(ident)), Some(TokenTree::Punct(punct))) => match punct.as_char() {
+            ',' => !is_option_like(&ident, options),
+            // `key = value`, but not `a == b` nor `a => b`.
+            '=' => match (punct.spacing(), tokens.next()) {
+                (Spacing::Joint, Some(TokenTree::Punct(next))) => {
+                    matches!(next.as_char(), '=' | '>')
+                }
+                _ => false,
+            },
+            _ => true,
+        },
+        _ => true,
+    }
+}
+
+/// Checks if an identifier is one of the given options, or a misspelling of one of them.
+fn is_option_like(ident: &Ident, options: &[&'static str]) -> bool {
+    options.iter().any(|option| ident == option) || suggest(ident, options).is_some()
+}
+
+/// Build the error for an unknown option, suggesting the intended one.
+fn unknown_option(path: &Path, options: &[&'static str]) -> syn::Error {
+    let name = path.to_token_stream().to_string().replace(' ', "");
+    let message = match path.get_ident().and_then(|ident| suggest(ident, options)) {
+        Some(option) => format!(
+            "unknown clone option `{}`, did you mean `{}`?",
+            name, option
+        ),
+        None => format!(
+            "unknown clone option `{}`, expected one of: {}",
+            name,
+            options
+                .iter()
+                .map(|option| format!("`{}`", option))
+                .collect::<Vec<_>>()
+                .join(", ")
+        ),
+    };
+    syn::Error::new_spanned(path, message)
+}
+
+/// Find the option an unknown identifier is a misspelling of, if any.
+fn suggest(ident: &Ident, options: &[&'static str]) -> Option<&'static str> {
+    let ident = ident.to_string();
+    options
+        .iter()
+        .map(|option| (edit_distance(&ident, option), *option))
+        .filter(|(distance, option)| *distance > 0 && *distance <= (option.len() / 3).max(1))
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, option)| option)
+}
+
+/// Number of single-character edits (insertion, deletion, substitution or transposition of two
+/// adjacent characters) needed to turn `a` into `b`.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    let mut distances = vec![vec![0; b.len() + 1]; a.len() + 1];
+    for (i, row) in distances.iter_mut().enumerate() {
+        row[0] = i;
+    }
+    for (j, distance) in distances[0].iter_mut().enumerate() {
+        *distance = j;
+    }
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            let cost = usize::from(a[i - 1] != b[j - 1]);
+            let mut distance = (distances[i - 1][j] + 1)
+                .min(distances[i][j - 1] + 1)
+                .min(distances[i - 1][j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                distance = distance.min(distances[i - 2][j - 2] + 1);
+            }
+            distances[i][j] = distance;
+        }
+    }
+    distances[a.len()][b.len()]
+}
@@ -0,0 +1,339 @@
+use std::collections::HashSet;
+
+use proc_macro2::{Ident, TokenStream, TokenTree};
+use quote::ToTokens;
+use syn::punctuated::Punctuated;
+use syn::{
+    parse_quote, GenericArgument, GenericParam, Generics, PathArguments, ReturnType, Type,
+    TypeParamBound, WherePredicate,
+};
+
+use crate::internals::ast::{Container, Data, Field};
+use crate::internals::attr::{FieldAttrs, Strategy};
+use crate::internals::Method;
+
+/// Add a `T: Clone` bound for each type parameter `T` used by a field that takes the standard
+/// `.clone()` path.
+///
+/// Parameters only used in overridden, defaulted or `clone_with` fields, or hidden behind a type
+/// which is `Clone` whatever they are (`PhantomData<T>`, `&T`, `*const T`, `fn(T)`), are left
+/// unbounded.
+///
+/// A `#[clone(bound = "...")]` attribute replaces the inferred bounds: on the container for the
+/// whole type, on a field (or an enum variant) for the bounds this field would require.
+///
+/// `method` is the generated method which requires the most bounds.
+pub(crate) fn with_clone_bound(container: &Container, method: Method) -> Generics {
+    let mut generics = container.generics.clone();
+    if let Some(predicates) = &container.attrs.bound {
+        generics
+            .make_where_clause()
+            .predicates
+            .extend(predicates.iter().cloned());
+        return generics;
+    }
+
+    let params: HashSet<&Ident> = generics.type_params().map(|param| &param.ident).collect();
+    let mut used = HashSet::new();
+    let mut explicit = Vec::new();
+    let mut fields: Vec<&Field> = Vec::new();
+    match &container.data {
+        Data::Struct(_, struct_fields) => fields.extend(struct_fields),
+        Data::Enum(variants) => {
+            for variant in variants {
+                if let Some(predicates) = &variant.attrs.bound {
+                    explicit.extend(predicates.iter().cloned());
+                } else if let Strategy::To(to) = variant.attrs.strategy_for(method) {
+                    // The fields moved to another variant are cloned.
+                    let to = variants.iter().find(|other| other.ident == to);
+                    fields.extend(variant.fields.iter().filter(|field| {
+                        to.is_some_and(|to| {
+                            to.fields.iter().any(|other| other.member == field.member)
+                        })
+                    }));
+                } else if is_cloned(&variant.attrs, method) {
+                    fields.extend(&variant.fields);
+                }
+            }
+        }
+    }
+    // The excluded fields are not part of a projection.
+    if let Method::Into(_) = method {
+        fields.retain(|field| field.attrs.exclude.is_none());
+    }
+    for field in fields {
+        if let Some(predicates) = &field.attrs.bound {
+            explicit.extend(predicates.iter().cloned());
+        } else if is_cloned(&field.attrs, method) {
+            collect_type_params(field.ty, &params, &mut used);
+        }
+    }
+    if used.is_empty() && explicit.is_empty() {
+        return generics;
+    }
+
+    // Keep the declaration order of the parameters so the generated where clause is stable.
+    let bounded: Vec<Ident> = generics
+        .type_params()
+        .filter(|param| used.contains(&param.ident))
+        .map(|param| param.ident.clone())
+        .collect();
+    let where_clause = generics.make_where_clause();
+    for ident in bounded {
+        // The nodes of a graph are kept in a table of `dyn Any`.
+        let predicate: WherePredicate = match method {
+            Method::GraphClone => parse_quote! { #ident: Clone + 'static },
+            _ => parse_quote! { #ident: Clone },
+        };
+        where_clause.predicates.push(predicate);
+    }
+    where_clause.predicates.extend(explicit);
+    generics
+}
+
+/// The generics of the `#[clone(into = ...)]` projection: the ones of the container, without the
+/// parameters (types, lifetimes and consts) used only by excluded fields, which the projection
+/// could not use, nor the bounds mentioning them.
+pub(crate) fn projection_generics(container: &Container) -> Generics {
+    let mut generics = container.generics.clone();
+    let fields: Vec<&Field> = match &container.data {
+        Data::Struct(_, fields) => fields.iter().collect(),
+        Data::Enum(variants) => variants
+            .iter()
+            .flat_map(|variant| &variant.fields)
+            .collect(),
+    };
+    let mut used = HashSet::new();
+    for field in fields
+        .into_iter()
+        .filter(|field| field.attrs.exclude.is_none())
+    {
+        collect_names(field.ty.to_token_stream(), &mut used);
+    }
+    let removed: HashSet<String> = generics
+        .params
+        .iter()
+        .map(param_name)
+        .filter(|name| !used.contains(name))
+        .collect();
+    if removed.is_empty() {
+        return generics;
+    }
+
+    let mentions_removed = |tokens: &dyn ToTokens| {
+        let mut names = HashSet::new();
+        collect_names(tokens.to_token_stream(), &mut names);
+        !names.is_disjoint(&removed)
+    };
+    generics.params = std::mem::take(&mut generics.params)
+        .into_iter()
+        .filter(|param| !removed.contains(&param_name(param)))
+        .map(|mut param| {
+            match &mut param {
+                GenericParam::Type(param) => {
+                    param.bounds = retain(&param.bounds, |bound| !mentions_removed(bound));
+                    if param
+                        .default
+                        .as_ref()
+                        .is_some_and(|ty| mentions_removed(ty))
+                    {
+                        param.default = None;
+                    }
+                }
+                GenericParam::Lifetime(param) => {
+                    param.bounds = retain(&param.bounds, |bound| !mentions_removed(bound));
+                }
+                GenericParam::Const(param) => {
+                    if param
+                        .default
+                        .as_ref()
+                        .is_some_and(|expr| mentions_removed(expr))
+                    {
+                        param.default = None;
+                    }
+                }
+            }
+            param
+        })
+        .collect();
+    // Drop the predicates bounding the removed parameters, and the bounds mentioning them.
+    if let Some(where_clause) = &mut generics.where_clause {
+        where_clause.predicates = std::mem::take(&mut where_clause.predicates)
+            .into_iter()
+            .filter_map(|mut predicate| {
+                match &mut predicate {
+                    WherePredicate::Type(predicate) => {
+                        if mentions_removed(&predicate.bounded_ty) {
+                            return None;
+                        }
+                        predicate.bounds =
+                            retain(&predicate.bounds, |bound| !mentions_removed(bound));
+                        if predicate.bounds.is_empty() {
+                            return None;
+                        }
+                    }
+                    WherePredicate::Lifetime(predicate) => {
+                        if mentions_removed(&predicate.lifetime) {
+                            return None;
+                        }
+                        predicate.bounds =
+                            retain(&predicate.bounds, |bound| !mentions_removed(bound));
+                        if predicate.bounds.is_empty() {
+                            return None;
+                        }
+                    }
+                    _ => {}
+                }
+                Some(predicate)
+            })
+            .collect();
+    }
+    generics
+}
+
+/// The name of a generic parameter, as found by [`collect_names`]: `T`, `'a` or `N`.
+fn param_name(param: &GenericParam) -> String {
+    match param {
+        GenericParam::Type(param) => param.ident.to_string(),
+        GenericParam::Lifetime(param) => param.lifetime.to_string(),
+        GenericParam::Const(param) => param.ident.to_string(),
+    }
+}
+
+/// Collect the identifiers and the lifetimes which appear in the given tokens, which covers the
+/// generic parameters used by a type, however they are used.
+fn collect_names(tokens: TokenStream, names: &mut HashSet<String>) {
+    let mut tokens = tokens.into_iter().peekable();
+    while let Some(token) = tokens.next() {
+        match token {
+            TokenTree::Ident(ident) => {
+                names.insert(ident.to_string());
+            }
+            TokenTree::Punct(punct) if punct.as_char() == '\'' => {
+                if let Some(TokenTree::Ident(ident)) = tokens.next() {
+                    names.insert(format!("'{}", ident));
+                }
+            }
+            TokenTree::Group(group) => collect_names(group.stream(), names),
+            _ => {}
+        }
+    }
+}
+
+/// The bounds which satisfy `keep`.
+fn retain<T: Clone, P: Default>(
+    bounds: &Punctuated<T, P>,
+    keep: impl Fn(&T) -> bool,
+) -> Punctuated<T, P> {
+    bounds.iter().filter(|bound| keep(bound)).cloned().collect()
+}
+
+/// Checks if a field (or an enum variant) is cloned (as usual or deeply) by the generated method:
+/// `with_ctx` and `try_with` ones are only cloned as usual by the methods which do not use them.
+fn is_cloned(attrs: &FieldAttrs, method: Method) -> bool {
+    match attrs.strategy_for(method) {
+        Strategy::Standard | Strategy::Deep | Strategy::Set(_) => true,
+        Strategy::WithCtx(_) => method != Method::CloneWithCtx,
+        Strategy::TryWith(_) => !matches!(method, Method::TryClone { .. }),
+        _ => false,
+    }
+}
+
+/// Collect the type parameters (from `params`) that appear in the given type.
+///
+/// The parameters are only collected where the type needs them to be `Clone`: the ones behind a
+/// `PhantomData`, a shared reference, a raw pointer or a function pointer are skipped.
+fn collect_type_params(ty: &Type, params: &HashSet<&Ident>, used: &mut HashSet<Ident>) {
+    match ty {
+        Type::Path(type_path) => {
+            if let Some(qself) = &type_path.qself {
+                collect_type_params(&qself.ty, params, used);
+            }
+            let path = &type_path.path;
+            // `PhantomData<T>` is `Clone` whatever `T` is.
+            if path
+                .segments
+                .last()
+                .is_some_and(|segment| segment.ident == "PhantomData")
+            {
+                return;
+            }
+            if type_path.qself.is_none() {
+                if let Some(first) = path.segments.first() {
+                    if params.contains(&first.ident) {
+                        used.insert(first.ident.clone());
+                    }
+                }
+            }
+            for segment in &path.segments {
+                collect_arguments_type_params(&segment.arguments, params, used);
+            }
+        }
+        Type::Array(array) => collect_type_params(&array.elem, params, used),
+        Type::Group(group) => collect_type_params(&group.elem, params, used),
+        Type::Paren(paren) => collect_type_params(&paren.elem, params, used),
+        // `&T`, `*const T`, `*mut T` and `fn(T) -> U` are `Clone` whatever `T` and `U` are. `&mut T`
+        // never is: its `T` is collected as in any other type, the clone fails anyway.
+        Type::Reference(reference) if reference.mutability.is_some() => {
+            collect_type_params(&reference.elem, params, used)
+        }
+        Type::Slice(slice) => collect_type_params(&slice.elem, params, used),
+        Type::Tuple(tuple) => {
+            for elem in &tuple.elems {
+                collect_type_params(elem, params, used);
+            }
+        }
+        Type::TraitObject(trait_object) => {
+            collect_bounds_type_params(trait_object.bounds.iter(), params, used)
+        }
+        Type::ImplTrait(impl_trait) => {
+            collect_bounds_type_params(impl_trait.bounds.iter(), params, used)
+        }
+        _ => {}
+    }
+}
+
+/// Collect the type parameters used in the generic arguments of a path segment: `Vec<T>`, `Fn(T) -> U`.
+fn collect_arguments_type_params(
+    arguments: &PathArguments,
+    params: &HashSet<&Ident>,
+    used: &mut HashSet<Ident>,
+) {
+    match arguments {
+        PathArguments::None => {}
+        PathArguments::AngleBracketed(arguments) => {
+            for argument in &arguments.args {
+                match argument {
+                    GenericArgument::Type(ty) => collect_type_params(ty, params, used),
+                    GenericArgument::AssocType(assoc) => {
+                        collect_type_params(&assoc.ty, params, used)
+                    }
+                    _ => {}
+                }
+            }
+        }
+        PathArguments::Parenthesized(arguments) => {
+            for input in &arguments.inputs {
+                collect_type_params(input, params, used);
+            }
+            if let ReturnType::Type(_, output) = &arguments.output {
+                collect_type_params(output, params, used);
+            }
+        }
+    }
+}
+
+/// Collect the type parameters used in trait bounds: `dyn Fn(T)`, `impl Iterator<Item = T>`.
+fn collect_bounds_type_params<'b>(
+    bounds: impl Iterator<Item = &'b TypeParamBound>,
+    params: &HashSet<&Ident>,
+    used: &mut HashSet<Ident>,
+) {
+    for bound in bounds {
+        if let TypeParamBound::Trait(trait_bound) = bound {
+            for segment in &trait_bound.path.segments {
+                collect_arguments_type_params(&segment.arguments, params, used);
+            }
+        }
+    }
+}
@@ -0,0 +1,143 @@
+use proc_macro2::{Ident, Span, TokenStream};
+use quote::{format_ident, quote};
+use syn::ext::IdentExt;
+use syn::{parse_quote, Generics, Lifetime, Member};
+
+use crate::internals::ast::{Container, Field};
+use crate::internals::field_smart_clone::clone_field;
+use crate::internals::Method;
+
+/// Generate the `<Name>CloneBuilder` of a `#[clone(builder)]` struct, along with the
+/// `clone_builder()` method creating it.
+///
+/// The builder borrows the source and holds an optional value per field: the fields which are not
+/// set are cloned by `build()` according to their `#[clone...]` options, the others are never
+/// cloned.
+///
+/// `generics` are the generics of the struct, bounded as for `Clone`.
+pub(crate) fn clone_builder(
+    container: &Container,
+    fields: &[Field],
+    generics: &Generics,
+) -> TokenStream {
+    let name = container.ident;
+    let vis = container.vis;
+    let builder = format_ident!("{}CloneBuilder", name);
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+
+    // The builder has the generics of the struct, plus the lifetime of the borrowed source, named
+    // apart from the lifetimes of the struct.
+    let lifetimes: Vec<_> = generics
+        .lifetimes()
+        .map(|param| param.lifetime.ident.unraw().to_string())
+        .collect();
+    let lifetime = Lifetime::new(
+        &format!("'{}", fresh_name("source", &lifetimes)),
+        Span::call_site(),
+    );
+    let mut builder_generics = generics.clone();
+    builder_generics
+        .params
+        .insert(0, parse_quote! { #lifetime });
+    let (builder_impl_generics, builder_ty_generics, builder_where_clause) =
+        builder_generics.split_for_impl();
+    let mut definition_generics = container.generics.clone();
+    definition_generics
+        .params
+        .insert(0, parse_quote! { #lifetime });
+    let (definition_generics, _, definition_where_clause) = definition_generics.split_for_impl();
+
+    let members: Vec<_> = fields.iter().map(|field| &field.member).collect();
+    let types: Vec<_> = fields.iter().map(|field| field.ty).collect();
+    // The field borrowing the source, named apart from the fields of the struct.
+    let names: Vec<_> = members
+        .iter()
+        .filter_map(|member| match member {
+            Member::Named(ident) => Some(ident.unraw().to_string()),
+            Member::Unnamed(_) => None,
+        })
+        .collect();
+    let source = Ident::new(&fresh_name("source", &names), Span::call_site());
+
+    // The fields which are not set are cloned by a hidden method of the struct, so that the
+    // `#[clone...]` options reading `self` read the source.
+    let helpers: Vec<_> = members
+        .iter()
+        .map(|member| format_ident!("clone_builder_{}", member))
+        .collect();
+    let helper_values = fields.iter().map(|field| {
+        let member = &field.member;
+        clone_field(
+            field,
+            Method::Clone,
+            quote! { &self.#member },
+            quote! { self },
+        )
+        .value
+    });
+
+    let setter_docs = members
+        .iter()
+        .map(|member| format!("Set `{}` instead of cloning it.", quote! { #member }));
+    let builder_doc = format!(
+        "A clone of a [`{}`] being built, where some fields are set instead of being cloned.",
+        name
+    );
+
+    quote! {
+        #[doc = #builder_doc]
+        #vis struct #builder #definition_generics #definition_where_clause {
+            #source: &#lifetime #name #ty_generics,
+            #(#members: ::core::option::Option<#types>,)*
+        }
+
+        impl #impl_generics #name #ty_generics #where_clause {
+            /// Start a clone of this value, where some fields can be set instead of being cloned.
+            #vis fn clone_builder<#lifetime>(&#lifetime self) -> #builder #builder_ty_generics {
+                #builder {
+                    #source: self,
+                    #(#members: ::core::option::Option::None,)*
+                }
+            }
+
+            #(
+                #[doc(hidden)]
+                fn #helpers(&self) -> #types {
+                    #helper_values
+                }
+            )*
+        }
+
+        impl #builder_impl_generics #builder #builder_ty_generics #builder_where_clause {
+            #(
+                #[doc = #setter_docs]
+                #vis fn #members(mut self, value: #types) -> Self {
+                    self.#members = ::core::option::Option::Some(value);
+                    self
+                }
+            )*
+
+            /// Build the clone: the fields which are not set are cloned from the source.
+            #vis fn build(self) -> #name #ty_generics {
+                #name {
+                    #(
+                        #members: match self.#members {
+                            ::core::option::Option::Some(value) => value,
+                            ::core::option::Option::None => self.#source.#helpers(),
+                        },
+                    )*
+                }
+            }
+        }
+    }
+}
+
+/// The first name among `base`, `base_`, `base__`... which is not `taken`, for an item added by
+/// the builder next to the ones of the struct.
+fn fresh_name(base: &str, taken: &[String]) -> String {
+    let mut name = base.to_string();
+    while taken.contains(&name) {
+        name.push('_');
+    }
+    name
+}
@@ -0,0 +1,244 @@
+use proc_macro2::{Ident, Span, TokenStream};
+use quote::quote;
+use syn::{GenericArgument, PathArguments, Type};
+
+/// How the shared pointers are cloned by a deep clone.
+#[derive(Clone, Copy, PartialEq)]
+pub(crate) enum Walk {
+    /// `#[clone(deep)]` and `deep_clone`: every `Rc` and `Arc` is allocated anew.
+    Deep,
+    /// `clone_graph`: every shared node is allocated anew once, the sharing is kept in the copy
+    /// and the `Weak` pointers are re-pointed at the new nodes, using the `memo` table.
+    Graph,
+}
+
+/// A layer of a field type walked through by a deep clone.
+enum Layer<'a> {
+    /// `Rc<T>` or `Arc<T>`, along with the name of the pointer.
+    Shared(Ident, &'a Type),
+    /// `Weak<T>`
+    Weak,
+    /// `Box<T>`
+    Box(&'a Type),
+    /// `Option<T>`
+    Option(&'a Type),
+    /// `Vec<T>`
+    Vec(&'a Type),
+    /// `RefCell<T>`
+    RefCell(&'a Type),
+    /// `Mutex<T>`
+    Mutex(&'a Type),
+    /// `RwLock<T>`
+    RwLock(&'a Type),
+}
+
+/// Deep clone a value of type `ty`, given by reference: the `Rc`, `Arc` and `Box` pointers are
+/// allocated anew through `Option`, `Vec`, `RefCell`, `Mutex` and `RwLock` layers, so that the
+/// clone shares nothing with the original.
+///
+/// The inner values are cloned with their inherent `deep_clone` (or `clone_graph_in`) method when
+/// they have one (when they derive `DeepClone` or `GraphClone`), otherwise with `Clone::clone`.
+pub(crate) fn deep_clone_value(ty: &Type, reference: TokenStream, walk: Walk) -> TokenStream {
+    let Some(layer) = layer(ty) else {
+        return deep_clone_leaf(reference, walk);
+    };
+    let memo = graph_memo();
+    match layer {
+        Layer::Shared(pointer, inner) if walk == Walk::Graph => {
+            let weak = match pointer == "Arc" {
+                true => quote! { ::std::sync::Weak<#inner> },
+                false => quote! { ::std::rc::Weak<#inner> },
+            };
+            let inner = deep_clone_value(inner, quote! { &**reference }, walk);
+            quote! {
+                {
+                    let reference = #reference;
+                    let key = <#ty>::as_ptr(reference) as *const () as usize;
+                    let node = #memo
+                        .get(&key)
+                        .and_then(|node| node.downcast_ref::<#weak>())
+                        .cloned();
+                    match node {
+                        Some(node) => node
+                            .upgrade()
+                            .expect("cannot clone a strong reference cycle"),
+                        None => <#ty>::new_cyclic(|node| {
+                            #memo.insert(key, ::std::boxed::Box::new(node.clone()));
+                            #inner
+                        }),
+                    }
+                }
+            }
+        }
+        Layer::Weak if walk == Walk::Graph => quote! {
+            {
+                let reference = #reference;
+                let key = <#ty>::as_ptr(reference) as *const () as usize;
+                match #memo.get(&key).and_then(|node| node.downcast_ref::<#ty>()) {
+                    Some(node) => node.clone(),
+                    None => reference.clone(),
+                }
+            }
+        },
+        Layer::Weak => deep_clone_leaf(reference, walk),
+        Layer::Shared(_, inner) | Layer::Box(inner) => {
+            let inner = deep_clone_value(inner, quote! { &**#reference }, walk);
+            quote! { <#ty>::new(#inner) }
+        }
+        Layer::Option(inner) => {
+            let inner = deep_clone_value(inner, quote! { value }, walk);
+            quote! { (#reference).as_ref().map(|value| #inner) }
+        }
+        Layer::Vec(inner) => {
+            let inner = deep_clone_value(inner, quote! { value }, walk);
+            quote! { (#reference).iter().map(|value| #inner).collect::<#ty>() }
+        }
+        Layer::RefCell(inner) => {
+            let inner = deep_clone_value(inner, quote! { &*(#reference).borrow() }, walk);
+            quote! { <#ty>::new(#inner) }
+        }
+        Layer::Mutex(inner) => {
+            let guard = quote! {
+                (#reference).lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+            };
+            let inner = deep_clone_value(inner, quote! { &*#guard }, walk);
+            quote! { <#ty>::new(#inner) }
+        }
+        Layer::RwLock(inner) => {
+            let guard = quote! {
+                (#reference).read().unwrap_or_else(|poisoned| poisoned.into_inner())
+            };
+            let inner = deep_clone_value(inner, quote! { &*#guard }, walk);
+            quote! { <#ty>::new(#inner) }
+        }
+    }
+}
+
+/// Checks if `clone_graph` re-points a `Weak` pointer in a value of type `ty`, found through the
+/// layers which are not a strong pointer: `Weak<T>`, `Option<Weak<T>>`, `RefCell<Vec<Weak<T>>>`...
+pub(crate) fn holds_weak(ty: &Type) -> bool {
+    match layer(ty) {
+        Some(Layer::Weak) => true,
+        Some(
+            Layer::Box(inner)
+            | Layer::Option(inner)
+            | Layer::Vec(inner)
+            | Layer::RefCell(inner)
+            | Layer::Mutex(inner)
+            | Layer::RwLock(inner),
+        ) => holds_weak(inner),
+        Some(Layer::Shared(..)) | None => false,
+    }
+}
+
+/// The `memo` parameter of `clone_graph_in`, hygienic so that a field of the same name, bound in a
+/// variant pattern, does not shadow it.
+pub(crate) fn graph_memo() -> Ident {
+    Ident::new("memo", Span::mixed_site())
+}
+
+/// The type of the table of the nodes already cloned by `clone_graph`, indexed by the address of
+/// the original node and holding a `Weak` pointer to the new one.
+pub(crate) fn graph_memo_type() -> TokenStream {
+    quote! {
+        ::std::collections::HashMap<usize, ::std::boxed::Box<dyn ::std::any::Any>>
+    }
+}
+
+/// Clone a value with its inherent `deep_clone` (or `clone_graph_in`) method if it has one,
+/// otherwise with `.clone()`.
+///
+/// Inherent methods take precedence over trait methods: the local trait is only a fallback for
+/// the types which do not derive `DeepClone` (or `GraphClone`). The method is resolved on the type
+/// as written in the struct: a value of a generic type `T` always falls back to `.clone()`, as no
+/// exported trait could bound `T`.
+fn deep_clone_leaf(reference: TokenStream, walk: Walk) -> TokenStream {
+    match walk {
+        Walk::Deep => quote! {
+            {
+                #[allow(dead_code)]
+                trait DeepCloneFallback: Clone {
+                    fn deep_clone(&self) -> Self {
+                        self.clone()
+                    }
+                }
+                impl<T: Clone> DeepCloneFallback for T {}
+                (#reference).deep_clone()
+            }
+        },
+        Walk::Graph => {
+            let memo = graph_memo();
+            let memo_type = graph_memo_type();
+            quote! {
+                {
+                    #[allow(dead_code)]
+                    trait GraphCloneFallback: Clone {
+                        fn clone_graph_in(&self, _memo: &mut #memo_type) -> Self {
+                            self.clone()
+                        }
+                    }
+                    impl<T: Clone> GraphCloneFallback for T {}
+                    (#reference).clone_graph_in(&mut *#memo)
+                }
+            }
+        }
+    }
+}
+
+/// Find the layer a type is made of, from the last segment of its path: `Arc<T>`,
+/// `std::sync::Arc<T>`...
+///
+/// The pointers to an unsized value (`Arc<str>`, `Box<[T]>`, `Rc<dyn Trait>`) cannot be allocated
+/// anew around a clone of it: they are no layer, and are cloned with `Clone::clone`.
+fn layer(ty: &Type) -> Option<Layer<'_>> {
+    let Type::Path(type_path) = ty else {
+        return None;
+    };
+    if type_path.qself.is_some() {
+        return None;
+    }
+    let segment = type_path.path.segments.last()?;
+    let PathArguments::AngleBracketed(arguments) = &segment.arguments else {
+        return None;
+    };
+    let mut types = arguments.args.iter().filter_map(|argument| match argument {
+        GenericArgument::Type(inner) => Some(inner),
+        _ => None,
+    });
+    let inner = types.next()?;
+    if types.next().is_some() {
+        return None;
+    }
+    match segment.ident.to_string().as_str() {
+        "Rc" | "Arc" | "Box" if is_unsized(inner) => None,
+        "Rc" | "Arc" => Some(Layer::Shared(segment.ident.clone(), inner)),
+        "Weak" => Some(Layer::Weak),
+        "Box" => Some(Layer::Box(inner)),
+        "Option" => Some(Layer::Option(inner)),
+        "Vec" => Some(Layer::Vec(inner)),
+        "RefCell" => Some(Layer::RefCell(inner)),
+        "Mutex" => Some(Layer::Mutex(inner)),
+        "RwLock" => Some(Layer::RwLock(inner)),
+        _ => None,
+    }
+}
+
+/// Checks if a type is known to be unsized: a slice, a trait object, or one of the unsized types
+/// of the standard library (`str`, `OsStr`, `Path`, `CStr`).
+fn is_unsized(ty: &Type) -> bool {
+    match ty {
+        Type::Slice(_) | Type::TraitObject(_) => true,
+        Type::Group(group) => is_unsized(&group.elem),
+        Type::Paren(paren) => is_unsized(&paren.elem),
+        Type::Path(type_path) => {
+            type_path.qself.is_none()
+                && type_path.path.segments.last().is_some_and(|segment| {
+                    segment.arguments.is_none()
+                        && ["str", "OsStr", "Path", "CStr"]
+                            .iter()
+                            .any(|name| segment.ident == name)
+                })
+        }
+        _ => false,
+    }
+}
@@ -1,73 +1,85 @@
-use proc_macro2::{Ident, TokenStream};
-use quote::quote;
-use syn::{DataEnum, Fields, FieldsNamed, FieldsUnnamed, LitStr, Meta, Token, Variant};
+use proc_macro2::{Ident, Span, TokenStream};
+use quote::{format_ident, quote, quote_spanned};
+use syn::ext::IdentExt;
+use syn::spanned::Spanned;
+use syn::Index;
 
-use crate::internals::CloneMode;
+use crate::internals::ast::{Field, Style, Variant};
+use crate::internals::attr::Strategy;
+use crate::internals::field_smart_clone::{
+    call_function, clone_field, is_cloned_last, never_clone, never_cloned_field, propagate_error,
+    reads_self, reset_field, ClonedField,
+};
+use crate::internals::{context_param, CloneMode, Method};
 
 /**
  * Clone an enum type.
  */
-pub(crate) fn clone_enum_type(identity: &Ident, fields: DataEnum) -> TokenStream {
-    let clone_variants = fields.variants.iter().map(|variant| {
-        // Check for the `#[clone...]` attribute
-        match &variant
-            .attrs
+pub(crate) fn clone_enum_type(
+    identity: &Ident,
+    variants: &[Variant],
+    method: Method,
+) -> TokenStream {
+    // The projection of `#[clone(into = ...)]` is built with the same variants.
+    let target = match method {
+        Method::Into(target) => target,
+        _ => identity,
+    };
+    let clone_variants = variants.iter().map(|variant| {
+        let mode = match variant.attrs.strategy_for(method) {
+            // Variant is computed from the context.
+            Strategy::WithCtx(func) if method == Method::CloneWithCtx => {
+                let ctx = context_param();
+                CloneMode::Overridden(call_function(func, quote! { self, &mut *#ctx }))
+            }
+            // Variant is computed by a fallible function.
+            Strategy::TryWith(func) if matches!(method, Method::TryClone { .. }) => {
+                let name = format!("variant `{}`", variant.ident);
+                let call = call_function(func, quote! { self });
+                CloneMode::Overridden(propagate_error(call, method, &name))
+            }
+            // Variant is not marked: clone it as usual.
+            Strategy::Standard | Strategy::WithCtx(_) | Strategy::TryWith(_) => CloneMode::Standard,
+            // Variant is marked: smart clone it!
+            Strategy::Value(value) => CloneMode::Expression(quote! { #value }),
+            Strategy::Default => CloneMode::Overridden(quote! { Default::default() }),
+            Strategy::CloneWith(func) => {
+                CloneMode::Overridden(call_function(func, quote! { self }))
+            }
+            // Variant is computed from its fields.
+            Strategy::WithFields(func) => CloneMode::WithFields(func.clone()),
+            // Variant is kept, its fields are reset.
+            Strategy::Reset => CloneMode::Reset,
+            // Variant must never be cloned.
+            Strategy::Never(never) => {
+                let name = format!("variant `{{}}::{}`", variant.ident);
+                CloneMode::Never(never_clone(never, method, &name))
+            }
+            // Variant is remapped to another one.
+            Strategy::To(to) => {
+                let to = variants
+                    .iter()
+                    .find(|other| other.ident == to)
+                    .expect("`to` is checked while reading the attributes");
+                return remap_variant(identity, target, variant, to, method);
+            }
+            // Only accepted on fields: rejected on variants while reading the attributes.
+            Strategy::WithParent(_) | Strategy::Deep | Strategy::Set(_) => {
+                unreachable!("field option on an enum variant")
+            }
+        };
+        // A field which must never be cloned fails the whole clone of the variant.
+        let kept = variant
+            .fields
             .iter()
-            .find(|attr| attr.path().is_ident("clone"))
-        {
-            // Field is not marked: clone it as usual.
-            None => clone_variant_fields(identity, variant, CloneMode::Standard),
-            // Field is marked: smart clone it!
-            Some(attr) => match &attr.meta {
-                // Handle `#[clone]` by cloning as usual
-                Meta::Path(_) => clone_variant_fields(identity, variant, CloneMode::Standard),
-                // Handle #[clone = value].
-                Meta::NameValue(item) => {
-                    let value = &item.value;
-                    clone_variant_fields(
-                        identity,
-                        variant,
-                        CloneMode::Overridden(quote! { #value }),
-                    )
-                }
-                // Handle `#[clone(item1, item2)]` as `#[clone(items)]`.
-                Meta::List(items) => {
-                    let tokens = &items.tokens;
-                    // Case #[clone(...)]
-                    let mut clone_value = None::<TokenStream>;
-                    let _ = items.parse_nested_meta(|meta| {
-                        // `#[clone(default)]` => clone with default value
-                        if meta.path.is_ident("default") {
-                            clone_value = Some(quote! {
-                                Default::default()
-                            });
-                        }
-                        // `#[clone(clone_with =func)]`
-                        if meta.path.is_ident("clone_with") && meta.input.peek(Token![=]) {
-                            let func: LitStr = meta.value()?.parse()?;
-                            let func: TokenStream = func.parse()?;
-                            clone_value = Some(quote! {
-                                #func(self)
-                            });
-                        }
-                        Ok(())
-                    });
-
-                    match clone_value {
-                        None => clone_variant_fields(
-                            identity,
-                            variant,
-                            CloneMode::Overridden(quote! { #tokens }),
-                        ),
-                        Some(value) => clone_variant_fields(
-                            identity,
-                            variant,
-                            CloneMode::Overridden(quote! { #value }),
-                        ),
-                    }
-                }
-            },
-        }
+            .filter(|field| !is_excluded(field, method));
+        let mode = match never_cloned_field(kept, method) {
+            Some((field, never)) if matches!(mode, CloneMode::Standard | CloneMode::Reset) => {
+                CloneMode::Never(never_clone(never, method, &field_name(variant, field)))
+            }
+            _ => mode,
+        };
+        clone_variant_fields(identity, target, variant, mode, method)
     });
 
     quote! {
@@ -78,79 +90,432 @@ pub(crate) fn clone_enum_type(identity: &Ident, fields: DataEnum) -> TokenStream
 }
 
 /**
- * Clone unit type variant.
+ * Remap a variant to another one:
+ * `Self::Running { job, .. } => Self::Pending { job: job.clone(), retries: Default::default() }`.
+ *
+ * The fields of the same name (or position) are moved across, cloned according to their own
+ * `#[clone...]` attribute, the other fields of the target variant are defaulted.
  */
-fn clone_variant_fields(identity: &Ident, variant: &Variant, mode: CloneMode) -> TokenStream {
-    match &variant.fields {
-        Fields::Unit => clone_unit_fields(identity, &variant.ident, &variant.fields, mode),
-        Fields::Unnamed(fields) => clone_unnamed_fields(identity, &variant.ident, fields, mode),
-        Fields::Named(fields) => clone_named_fields(identity, &variant.ident, fields, mode),
+fn remap_variant(
+    identity: &Ident,
+    target: &Ident,
+    variant: &Variant,
+    to: &Variant,
+    method: Method,
+) -> TokenStream {
+    let variant_name = variant.ident;
+    // A moved field which must never be cloned fails the whole clone of the variant.
+    let moved = variant.fields.iter().filter(|field| {
+        to.fields
+            .iter()
+            .any(|to_field| to_field.member == field.member)
+    });
+    if let Some((field, never)) = never_cloned_field(moved, method) {
+        let value = never_clone(never, method, &field_name(variant, field));
+        return clone_variant_fields(identity, target, variant, CloneMode::Never(value), method);
     }
+
+    let mut bound = Vec::new();
+    let values: Vec<_> = to
+        .fields
+        .iter()
+        .map(|to_field| {
+            let Some(field) = variant
+                .fields
+                .iter()
+                .find(|field| field.member == to_field.member)
+            else {
+                // Spanned at the type, for the error to point at the field missing `Default`.
+                let ty = to_field.ty;
+                return quote_spanned! { ty.span() => <#ty as ::core::default::Default>::default() };
+            };
+            let binding = field.binding();
+            let cloned = clone_field(field, method, quote! { #binding }, quote! { self });
+            if cloned.uses_original {
+                bound.push(binding);
+            }
+            cloned.value
+        })
+        .collect();
+
+    let pattern = match variant.style {
+        Style::Unit => quote! { #identity::#variant_name },
+        Style::Unnamed => {
+            let bindings = variant.fields.iter().map(|field| {
+                let binding = field.binding();
+                match bound.contains(&binding) {
+                    true => quote! { #binding },
+                    false => quote! { _ },
+                }
+            });
+            quote! { #identity::#variant_name(#(#bindings),*) }
+        }
+        Style::Named => {
+            let rest = match bound.len() < variant.fields.len() {
+                true => Some(quote! { .. }),
+                false => None,
+            };
+            let bindings = bound.iter().map(|binding| quote! { #binding }).chain(rest);
+            quote! { #identity::#variant_name { #(#bindings),* } }
+        }
+    };
+    let to_name = to.ident;
+    let value = match to.style {
+        Style::Unit => quote! { #target::#to_name },
+        Style::Unnamed => quote! { #target::#to_name(#(#values),*) },
+        Style::Named => {
+            let members = to.fields.iter().map(|field| &field.member);
+            quote! { #target::#to_name { #(#members: #values),* } }
+        }
+    };
+    variant_arm(variant, pattern, value, false)
+}
+
+/**
+ * Clone an enum type into an existing value: when both sides are the same variant, its fields are
+ * reused in place, otherwise the source is cloned as usual.
+ *
+ * Returns `None` to keep the default `clone_from` when no variant can be reused.
+ */
+pub(crate) fn clone_from_enum_type(identity: &Ident, variants: &[Variant]) -> Option<TokenStream> {
+    // Hygienic, so that a field named `source` does not shadow the parameter.
+    let source = Ident::new("source", Span::mixed_site());
+    let clone_from_variants: Vec<_> = variants
+        .iter()
+        .filter(|variant| variant.attrs.is_standard())
+        .filter_map(|variant| clone_from_variant_fields(identity, variant, &source))
+        .collect();
+
+    match clone_from_variants.is_empty() {
+        true => None,
+        false => Some(quote! {
+            fn clone_from(&mut self, #source: &Self) {
+                match (&mut *self, #source) {
+                    #(#clone_from_variants,)*
+                    _ => *self = #source.clone(),
+                }
+            }
+        }),
+    }
+}
+
+/**
+ * Clone the fields of a variant in place: `(Self::B(self_v0, self_v1), Self::B(v0, v1)) => { ... }`.
+ *
+ * Returns `None` for unit variants, when an override reads `self`, which would be the destination
+ * here, and when a field must never be cloned.
+ *
+ * The fields of the source keep their names, which the overrides may read, while the destination
+ * bindings are hygienic and cannot collide with them.
+ */
+fn clone_from_variant_fields(
+    identity: &Ident,
+    variant: &Variant,
+    parent: &Ident,
+) -> Option<TokenStream> {
+    if never_cloned_field(&variant.fields, Method::Clone).is_some() {
+        return None;
+    }
+    let variant_name = &variant.ident;
+    let mut destinations = Vec::new();
+    let mut sources = Vec::new();
+    let mut assignments = Vec::new();
+    for field in &variant.fields {
+        let source = field.binding();
+        let destination = format_ident!("self_{}", source.unraw(), span = Span::mixed_site());
+        let cloned = clone_field(field, Method::Clone, quote! { #source }, quote! { #parent });
+        if reads_self(field) {
+            return None;
+        }
+        match field.attrs.is_standard() {
+            true => assignments.push(quote! { #destination.clone_from(#source); }),
+            false => {
+                let value = cloned.value;
+                assignments.push(quote! { *#destination = #value; });
+            }
+        }
+        destinations.push(destination);
+        sources.push(match cloned.uses_original {
+            true => Some(source),
+            false => None,
+        });
+    }
+
+    match variant.style {
+        Style::Unit => None,
+        Style::Unnamed => {
+            let sources = sources.iter().map(|source| match source {
+                Some(source) => quote! { #source },
+                None => quote! { _ },
+            });
+            Some(quote! {
+                (#identity::#variant_name(#(#destinations),*), #identity::#variant_name(#(#sources),*)) => {
+                    #(#assignments)*
+                }
+            })
+        }
+        Style::Named => {
+            let field_idents = variant.fields.iter().map(|field| &field.member);
+            let rest = match sources.iter().any(Option::is_none) {
+                true => Some(quote! { .. }),
+                false => None,
+            };
+            let sources = sources
+                .iter()
+                .flatten()
+                .map(|source| quote! { #source })
+                .chain(rest);
+            Some(quote! {
+                (#identity::#variant_name { #(#field_idents: #destinations),* }, #identity::#variant_name { #(#sources),* }) => {
+                    #(#assignments)*
+                }
+            })
+        }
+    }
+}
+
+/**
+ * Clone unit type variant.
+ *
+ * The variant is matched on `identity` and built on `target`, which differ for a projection.
+ */
+fn clone_variant_fields(
+    identity: &Ident,
+    target: &Ident,
+    variant: &Variant,
+    mode: CloneMode,
+    method: Method,
+) -> TokenStream {
+    let fields = &variant.fields;
+    let never = matches!(mode, CloneMode::Never(_));
+    let (pattern, value) = match variant.style {
+        Style::Unit => clone_unit_fields(identity, target, variant.ident, mode),
+        Style::Unnamed => {
+            clone_unnamed_fields(identity, target, variant.ident, fields, mode, method)
+        }
+        Style::Named => clone_named_fields(identity, target, variant.ident, fields, mode, method),
+    };
+    variant_arm(variant, pattern, value, never)
+}
+
+/**
+ * Build the match arm of a variant, applying the `map` function of the variant, if any, to its
+ * cloned value: a variant which is never cloned has nothing to map.
+ */
+fn variant_arm(
+    variant: &Variant,
+    pattern: TokenStream,
+    value: TokenStream,
+    never: bool,
+) -> TokenStream {
+    let value = match &variant.attrs.map {
+        Some(func) if !never => call_function(func, value),
+        _ => value,
+    };
+    quote! { #pattern => #value }
 }
 
 /**
  * Clone an unit field type: `A` annotated using smart clone #[clone...].
+ *
+ * Returns the pattern matching the variant and its cloned value.
  */
 fn clone_unit_fields(
     identity: &Ident,
+    target: &Ident,
     variant: &Ident,
-    _: &Fields,
     mode: CloneMode,
-) -> TokenStream {
+) -> (TokenStream, TokenStream) {
+    let pattern = quote! { #identity::#variant };
     match mode {
-        CloneMode::Standard => quote! { #identity::#variant => #identity::#variant },
-        CloneMode::Overridden(value) => quote! { #identity::#variant => #value },
+        CloneMode::Standard | CloneMode::Reset => (pattern, quote! { #target::#variant }),
+        CloneMode::Overridden(value) | CloneMode::Expression(value) | CloneMode::Never(value) => {
+            (pattern, value)
+        }
+        CloneMode::WithFields(func) => (pattern, call_function(&func, quote! {})),
     }
 }
 
 /**
  * Clone an unnamed field type: `B(i32, u32)` annotated using smart clone #[clone...].
+ *
+ * Returns the pattern matching the variant and its cloned value.
  */
 fn clone_unnamed_fields(
     identity: &Ident,
+    target: &Ident,
     variant: &Ident,
-    fields: &FieldsUnnamed,
+    fields: &[Field],
     mode: CloneMode,
-) -> TokenStream {
-    // Construction of the fields identities (v0, v1, ....).
-    let field_idents: Vec<_> = fields
-        .unnamed
-        .iter()
-        .enumerate()
-        .map(|(i, _)| Ident::new(&format!("v{}", i), proc_macro2::Span::call_site()))
-        .collect();
-
+    method: Method,
+) -> (TokenStream, TokenStream) {
     match mode {
-        CloneMode::Standard => {
-            quote! { #identity::#variant(#(#field_idents),*) => #identity::#variant(#(#field_idents.clone()),* ) }
+        CloneMode::Standard | CloneMode::Reset => {
+            // Clone each field according to its own `#[clone...]` attribute, binding only the
+            // fields (v0, v1, ....) whose original value is read.
+            let mut cloned_fields = Vec::new();
+            let bindings: Vec<_> = fields
+                .iter()
+                .map(|field| {
+                    let field_ident = field.binding();
+                    if is_excluded(field, method) {
+                        return quote! { _ };
+                    }
+                    let cloned = clone_variant_field(field, &field_ident, &mode, method);
+                    cloned_fields.push((field, cloned.value));
+                    match cloned.uses_original {
+                        true => quote! { #field_ident },
+                        false => quote! { _ },
+                    }
+                })
+                .collect();
+            let pattern = quote! { #identity::#variant(#(#bindings),*) };
+            match cloned_fields
+                .iter()
+                .any(|(field, _)| is_cloned_last(field, method))
+            {
+                // The fields holding a `Weak` pointer are set last, by position.
+                true => {
+                    let mut ordered: Vec<_> = cloned_fields.into_iter().enumerate().collect();
+                    ordered.sort_by_key(|(_, (field, _))| is_cloned_last(field, method));
+                    let cloned_fields = ordered.into_iter().map(|(position, (_, value))| {
+                        let position = Index::from(position);
+                        quote! { #position: #value }
+                    });
+                    (
+                        pattern,
+                        quote! { #target::#variant { #(#cloned_fields),* } },
+                    )
+                }
+                false => {
+                    let cloned_fields = cloned_fields.into_iter().map(|(_, value)| value);
+                    (pattern, quote! { #target::#variant(#(#cloned_fields),* ) })
+                }
+            }
+        }
+        CloneMode::Overridden(value) | CloneMode::Never(value) => {
+            (quote! { #identity::#variant(..) }, value)
+        }
+        // The fields are bound as `v0, v1...` for the expression to read them.
+        CloneMode::Expression(value) => {
+            let bindings = fields.iter().map(Field::binding);
+            let pattern = quote! {
+                #[allow(unused_variables)]
+                #identity::#variant(#(#bindings),*)
+            };
+            (pattern, value)
+        }
+        CloneMode::WithFields(func) => {
+            let bindings: Vec<_> = fields.iter().map(Field::binding).collect();
+            let call = call_function(&func, quote! { #(#bindings),* });
+            (quote! { #identity::#variant(#(#bindings),*) }, call)
         }
-        CloneMode::Overridden(value) => quote! { #identity::#variant(..) => #value },
     }
 }
 
 /**
  * Clone a named field type: `Point { x: u8, y: u8, ... }` annotated using smart clone #[clone...].
+ *
+ * Returns the pattern matching the variant and its cloned value.
  */
 fn clone_named_fields(
     identity: &Ident,
+    target: &Ident,
     variant: &Ident,
-    fields: &FieldsNamed,
+    fields: &[Field],
     mode: CloneMode,
-) -> TokenStream {
+    method: Method,
+) -> (TokenStream, TokenStream) {
     // Construction of the fields identities (x, y, ...).
-    let field_idents: Vec<_> = fields.named.iter().map(|f| &f.ident).collect();
+    let field_idents: Vec<_> = fields.iter().map(|field| field.binding()).collect();
 
     match mode {
-        CloneMode::Overridden(value) => {
-            quote! {  #identity::#variant { #(#field_idents),* } => #value }
-        }
-        CloneMode::Standard => {
-            // Loop through the fields of the named fields and clone it appropriately.
-            let cloned_fields = fields.named.iter().map(|field| {
-                let field_name = &field.ident;
-                quote! { #field_name.clone() }
-            });
-            quote! {  #identity::#variant { #(#field_idents),* } => #identity::#variant { #(#field_idents: #cloned_fields),* } }
+        CloneMode::Overridden(value) | CloneMode::Never(value) => {
+            (quote! { #identity::#variant { .. } }, value)
+        }
+        // The fields are bound by name for the expression to read them.
+        CloneMode::Expression(value) => {
+            let pattern = quote! {
+                #[allow(unused_variables)]
+                #identity::#variant { #(#field_idents),* }
+            };
+            (pattern, value)
         }
+        CloneMode::WithFields(func) => {
+            let call = call_function(&func, quote! { #(#field_idents),* });
+            (quote! { #identity::#variant { #(#field_idents),* } }, call)
+        }
+        CloneMode::Standard | CloneMode::Reset => {
+            // Loop through the fields of the named fields and clone it appropriately, binding
+            // only the fields whose original value is read.
+            let mut bindings = Vec::new();
+            let mut rest = None;
+            let mut members = Vec::new();
+            let mut cloned_fields = Vec::new();
+            for (field, field_name) in fields.iter().zip(&field_idents) {
+                if is_excluded(field, method) {
+                    rest = Some(quote! { .. });
+                    continue;
+                }
+                let cloned = clone_variant_field(field, field_name, &mode, method);
+                match cloned.uses_original {
+                    true => bindings.push(field_name),
+                    false => rest = Some(quote! { .. }),
+                }
+                members.push((is_cloned_last(field, method), field_name));
+                cloned_fields.push(cloned.value);
+            }
+            // The fields holding a `Weak` pointer are cloned last by `clone_graph`.
+            let mut ordered: Vec<_> = members.into_iter().zip(cloned_fields).collect();
+            ordered.sort_by_key(|((last, _), _)| *last);
+            let (members, cloned_fields): (Vec<_>, Vec<_>) = ordered
+                .into_iter()
+                .map(|((_, member), value)| (member, value))
+                .unzip();
+            let bindings = bindings
+                .iter()
+                .map(|binding| quote! { #binding })
+                .chain(rest);
+            (
+                quote! { #identity::#variant { #(#bindings),* } },
+                quote! { #target::#variant { #(#members: #cloned_fields),* } },
+            )
+        }
+    }
+}
+
+/**
+ * Clone a field of a variant bound to `binding`, or reset it when the whole variant is reset.
+ */
+fn clone_variant_field(
+    field: &Field,
+    binding: &Ident,
+    mode: &CloneMode,
+    method: Method,
+) -> ClonedField {
+    let (reference, parent) = (quote! { #binding }, quote! { self });
+    match mode {
+        CloneMode::Reset => reset_field(field, method, reference, parent),
+        _ => clone_field(field, method, reference, parent),
     }
 }
+
+/**
+ * The name of a field of a variant in the message of a failed clone, `{}` standing for the name of
+ * the type: ``field `x` of `{}::Point` ``.
+ */
+fn field_name(variant: &Variant, field: &Field) -> String {
+    let member = &field.member;
+    format!(
+        "field `{}` of `{{}}::{}`",
+        quote! { #member },
+        variant.ident
+    )
+}
+
+/**
+ * Checks if a field is left out of the value being built: the excluded fields of a projection.
+ */
+fn is_excluded(field: &Field, method: Method) -> bool {
+    matches!(method, Method::Into(_)) && field.attrs.exclude.is_some()
+}
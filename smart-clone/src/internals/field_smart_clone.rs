@@ -0,0 +1,264 @@
+use proc_macro2::{Ident, Span, TokenStream};
+use quote::{quote, quote_spanned, ToTokens};
+use syn::spanned::Spanned;
+use syn::Expr;
+
+use crate::internals::ast::Field;
+use crate::internals::attr::{Assignment, Never, Strategy};
+use crate::internals::deep_smart_clone::{deep_clone_value, holds_weak, Walk};
+use crate::internals::{context_param, mentions_self, Method};
+
+/// The cloned value of a field.
+pub(crate) struct ClonedField {
+    /// Expression computing the cloned value.
+    pub value: TokenStream,
+    /// Whether the expression reads the original value of the field.
+    pub uses_original: bool,
+}
+
+/// Clone a field according to its `#[clone...]` options, within the generated `method`.
+///
+/// `reference` borrows the original value of the field (`&self.x` for a struct, the `x` binding
+/// for an enum variant) and `parent` is the whole original value (`self`, or `source` in
+/// `clone_from`).
+///
+/// The `map` function of the field, if any, is applied to the value computed by its strategy.
+pub(crate) fn clone_field(
+    field: &Field,
+    method: Method,
+    reference: TokenStream,
+    parent: TokenStream,
+) -> ClonedField {
+    let cloned = clone_field_value(field, method, reference, parent);
+    match (&field.attrs.map, field.attrs.strategy_for(method)) {
+        // A value which is never cloned has nothing to map.
+        (Some(_), Strategy::Never(_)) | (None, _) => cloned,
+        (Some(func), _) => ClonedField {
+            value: call_function(func, cloned.value),
+            uses_original: cloned.uses_original,
+        },
+    }
+}
+
+/// Compute the value of a field according to its strategy, before it is mapped.
+fn clone_field_value(
+    field: &Field,
+    method: Method,
+    reference: TokenStream,
+    parent: TokenStream,
+) -> ClonedField {
+    match field.attrs.strategy_for(method) {
+        Strategy::WithCtx(func) if method == Method::CloneWithCtx => {
+            let ctx = context_param();
+            ClonedField {
+                value: call_function(func, quote! { #reference, &mut *#ctx }),
+                uses_original: true,
+            }
+        }
+        Strategy::TryWith(func) if matches!(method, Method::TryClone { .. }) => {
+            let member = &field.member;
+            let name = format!("field `{}`", quote! { #member });
+            ClonedField {
+                value: propagate_error(call_function(func, reference), method, &name),
+                uses_original: true,
+            }
+        }
+        Strategy::Set(assignments) => {
+            let cloned = match method {
+                Method::GraphClone => deep_clone_value(field.ty, reference, Walk::Graph),
+                Method::DeepClone => deep_clone_value(field.ty, reference, Walk::Deep),
+                _ => quote! { ::core::clone::Clone::clone(#reference) },
+            };
+            ClonedField {
+                value: assign_places(cloned, assignments),
+                uses_original: true,
+            }
+        }
+        Strategy::Standard | Strategy::Deep if method == Method::GraphClone => ClonedField {
+            value: deep_clone_value(field.ty, reference, Walk::Graph),
+            uses_original: true,
+        },
+        Strategy::Standard if method == Method::DeepClone => ClonedField {
+            value: deep_clone_value(field.ty, reference, Walk::Deep),
+            uses_original: true,
+        },
+        Strategy::Deep => ClonedField {
+            value: deep_clone_value(field.ty, reference, Walk::Deep),
+            uses_original: true,
+        },
+        // Called on the reference, as `Clone` resolves `x.clone()` to `T::clone` for an `&T` field.
+        Strategy::Standard | Strategy::WithCtx(_) | Strategy::TryWith(_) => ClonedField {
+            value: quote! { ::core::clone::Clone::clone(#reference) },
+            uses_original: true,
+        },
+        Strategy::Value(value) => ClonedField {
+            value: quote! { #value },
+            uses_original: false,
+        },
+        Strategy::Default => ClonedField {
+            value: quote! { Default::default() },
+            uses_original: false,
+        },
+        Strategy::CloneWith(func) => ClonedField {
+            value: call_function(func, reference),
+            uses_original: true,
+        },
+        Strategy::WithParent(func) => ClonedField {
+            value: call_function(func, quote! { #reference, #parent }),
+            uses_original: true,
+        },
+        Strategy::Never(never) => {
+            let member = &field.member;
+            let name = format!("field `{}` of `{{}}`", quote! { #member });
+            ClonedField {
+                value: never_clone(never, method, &name),
+                uses_original: false,
+            }
+        }
+        // Only accepted on variants: rejected on fields while reading the attributes.
+        Strategy::To(_) | Strategy::Reset | Strategy::WithFields(_) => {
+            unreachable!("variant option on a field")
+        }
+    }
+}
+
+/// Reset a field, instead of cloning it: to its override when it has one in the generated
+/// `method` (`default`, `value`, `with`...), otherwise to `Default::default()`.
+///
+/// The arguments are the ones of [`clone_field`].
+pub(crate) fn reset_field(
+    field: &Field,
+    method: Method,
+    reference: TokenStream,
+    parent: TokenStream,
+) -> ClonedField {
+    let overridden = match field.attrs.strategy_for(method) {
+        Strategy::Standard | Strategy::Deep => false,
+        Strategy::WithCtx(_) => method == Method::CloneWithCtx,
+        Strategy::TryWith(_) => matches!(method, Method::TryClone { .. }),
+        _ => true,
+    };
+    if overridden {
+        return clone_field(field, method, reference, parent);
+    }
+    // Spanned at the type, for the error to point at the field missing `Default`.
+    let ty = field.ty;
+    ClonedField {
+        value: quote_spanned! { ty.span() => <#ty as ::core::default::Default>::default() },
+        uses_original: false,
+    }
+}
+
+/// Checks if the expression or the function overriding a field reads `self`, which would be the
+/// destination in `clone_from`.
+pub(crate) fn reads_self(field: &Field) -> bool {
+    let map = field.attrs.map.as_ref();
+    if map.is_some_and(|func| mentions_self(&func.to_token_stream())) {
+        return true;
+    }
+    match &field.attrs.strategy {
+        Strategy::Value(expr)
+        | Strategy::CloneWith(expr)
+        | Strategy::WithParent(expr)
+        | Strategy::WithFields(expr)
+        | Strategy::WithCtx(expr)
+        | Strategy::TryWith(expr) => mentions_self(&expr.to_token_stream()),
+        Strategy::Standard
+        | Strategy::Default
+        | Strategy::Deep
+        | Strategy::To(_)
+        | Strategy::Reset
+        | Strategy::Never(_) => false,
+        Strategy::Set(assignments) => assignments
+            .iter()
+            .any(|assignment| mentions_self(&assignment.value.to_token_stream())),
+    }
+}
+
+/// Checks if a field is cloned after the other fields of its struct or variant: in `clone_graph`,
+/// the `Weak` pointers are re-pointed at the nodes cloned before them, which the other fields may
+/// hold whatever their order.
+pub(crate) fn is_cloned_last(field: &Field, method: Method) -> bool {
+    let walked = matches!(
+        field.attrs.strategy_for(method),
+        Strategy::Standard | Strategy::Deep | Strategy::Set(_)
+    );
+    method == Method::GraphClone && walked && holds_weak(field.ty)
+}
+
+/// The first field of `fields` which must never be cloned in the generated `method`, along with
+/// the way it fails to be cloned: the whole value then fails to be cloned instead of being built.
+pub(crate) fn never_cloned_field<'f, 'a: 'f>(
+    fields: impl IntoIterator<Item = &'f Field<'a>>,
+    method: Method,
+) -> Option<(&'f Field<'a>, &'f Never)> {
+    fields
+        .into_iter()
+        .find_map(|field| match field.attrs.strategy_for(method) {
+            Strategy::Never(never) => Some((field, never)),
+            _ => None,
+        })
+}
+
+/// Fail to clone a field or a variant which must never be cloned: `name` names it, with `{}`
+/// standing for the name of the type.
+///
+/// `forbid` returns an error from `try_clone`, converted from the message as a `String`; the other
+/// methods panic.
+pub(crate) fn never_clone(never: &Never, method: Method, name: &str) -> TokenStream {
+    let message = format!("cannot clone {}", name);
+    let type_name = quote! { ::core::any::type_name::<Self>() };
+    match never {
+        Never::Panic(reason) => {
+            let message = format!("{}: {{}}", message);
+            quote! { ::core::panic!(#message, #type_name, #reason) }
+        }
+        Never::Unreachable => quote! { ::core::unreachable!(#message, #type_name) },
+        Never::Forbid if matches!(method, Method::TryClone { .. }) => quote! {
+            return ::core::result::Result::Err(::core::convert::From::from(
+                ::std::format!(#message, #type_name)
+            ))
+        },
+        Never::Forbid => quote! { ::core::panic!(#message, #type_name) },
+    }
+}
+
+/// Assign the nested places of a `set(...)` option on the cloned value of a field:
+/// `{ let mut value = self.x.clone(); value.a.b = v; value }`.
+fn assign_places(cloned: TokenStream, assignments: &[Assignment]) -> TokenStream {
+    // Resolved at the macro, so that the assigned expressions cannot read it.
+    let value = Ident::new("value", Span::mixed_site());
+    let places = assignments.iter().map(|assignment| &assignment.place);
+    let values = assignments.iter().map(|assignment| &assignment.value);
+    quote! {
+        {
+            let mut #value = #cloned;
+            #(#value.#places = #values;)*
+            #value
+        }
+    }
+}
+
+/// Call the function of a `with = ...` option, which can be a path or a closure.
+pub(crate) fn call_function(func: &Expr, arguments: TokenStream) -> TokenStream {
+    match func {
+        Expr::Closure(_) => quote! { (#func)(#arguments) },
+        _ => quote! { #func(#arguments) },
+    }
+}
+
+/// Propagate the error of a `try_with` function with `?`: converted into the declared error type,
+/// or boxed along with a message naming what failed to be cloned.
+pub(crate) fn propagate_error(call: TokenStream, method: Method, name: &str) -> TokenStream {
+    match method {
+        Method::TryClone { boxed: true } => {
+            let message = format!("failed to clone {}: {{}}", name);
+            quote! {
+                #call.map_err(|error| -> ::std::boxed::Box<dyn ::std::error::Error + ::core::marker::Send + ::core::marker::Sync> {
+                    ::std::format!(#message, error).into()
+                })?
+            }
+        }
+        _ => quote! { #call? },
+    }
+}
@@ -0,0 +1,204 @@
+use proc_macro2::{Ident, Literal, Span, TokenStream};
+use quote::{format_ident, quote};
+use syn::{Generics, Member};
+
+use crate::internals::ast::{Container, Field, MAX_MASKABLE_FIELDS};
+use crate::internals::field_smart_clone::{clone_field, reset_field};
+use crate::internals::Method;
+
+/// Generate the `clone_except` and `clone_only` methods of a `#[clone(mask)]` struct, along with
+/// the `<Name>Field` enum listing its maskable fields and the `<Name>FieldSet` of them.
+///
+/// A masked field is reset to its override when it has one (`default`, `value`, `with`...),
+/// otherwise to `Default::default()`: a maskable field without override must implement `Default`.
+/// The fields marked with `keep` are always cloned as `Clone` does.
+///
+/// `generics` are the generics of the struct, bounded as for `Clone`.
+pub(crate) fn clone_mask(
+    container: &Container,
+    fields: &[Field],
+    generics: &Generics,
+) -> TokenStream {
+    let name = container.ident;
+    let vis = container.vis;
+    let field_enum = format_ident!("{}Field", name);
+    let field_set = format_ident!("{}FieldSet", name);
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+    // Hygienic, so that the overrides can call an item named `mask`.
+    let mask = Ident::new("mask", Span::mixed_site());
+
+    let maskable: Vec<_> = fields
+        .iter()
+        .filter(|field| field.attrs.keep.is_none())
+        .collect();
+    let variants: Vec<_> = maskable
+        .iter()
+        .map(|field| variant_name(&field.member))
+        .collect();
+    let bits = (0..maskable.len()).map(|index| Literal::u64_suffixed(1 << index));
+    let all = match maskable.len() {
+        MAX_MASKABLE_FIELDS => Literal::u64_suffixed(u64::MAX),
+        count => Literal::u64_suffixed((1 << count) - 1),
+    };
+
+    let cloned_fields = fields.iter().map(|field| {
+        let member = &field.member;
+        let cloned = clone_field(
+            field,
+            Method::Clone,
+            quote! { &self.#member },
+            quote! { self },
+        )
+        .value;
+        if field.attrs.keep.is_some() {
+            return quote! { #member: #cloned };
+        }
+        let variant = variant_name(member);
+        let masked = reset_field(
+            field,
+            Method::Clone,
+            quote! { &self.#member },
+            quote! { self },
+        )
+        .value;
+        quote! {
+            #member: match #mask.contains(#field_enum::#variant) {
+                true => #masked,
+                false => #cloned,
+            }
+        }
+    });
+
+    let enum_doc = format!(
+        "A field of a [`{}`], which can be reset by `clone_except` and `clone_only`.",
+        name
+    );
+    let set_doc = format!("A set of [`{}`], as a bit set.", field_enum);
+
+    quote! {
+        #[doc = #enum_doc]
+        #[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+        #vis enum #field_enum {
+            #(#variants,)*
+        }
+
+        impl #field_enum {
+            /// The bit of this field in a set.
+            const fn bit(self) -> u64 {
+                match self {
+                    #(Self::#variants => #bits,)*
+                }
+            }
+        }
+
+        #[doc = #set_doc]
+        #[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Default)]
+        #vis struct #field_set {
+            bits: u64,
+        }
+
+        impl #field_set {
+            /// The set of no field.
+            #vis const fn empty() -> Self {
+                Self { bits: 0 }
+            }
+
+            /// The set of all the fields.
+            #vis const fn all() -> Self {
+                Self { bits: #all }
+            }
+
+            /// Checks if the set contains the field.
+            #vis const fn contains(self, field: #field_enum) -> bool {
+                self.bits & field.bit() != 0
+            }
+
+            /// The set with the field added.
+            #vis const fn with(self, field: #field_enum) -> Self {
+                Self { bits: self.bits | field.bit() }
+            }
+
+            /// The set with the field removed.
+            #vis const fn without(self, field: #field_enum) -> Self {
+                Self { bits: self.bits & !field.bit() }
+            }
+
+            /// The set of the fields which are not in this one.
+            #vis const fn complement(self) -> Self {
+                Self { bits: !self.bits & #all }
+            }
+        }
+
+        impl ::core::convert::From<#field_enum> for #field_set {
+            fn from(field: #field_enum) -> Self {
+                Self::empty().with(field)
+            }
+        }
+
+        impl ::core::ops::BitOr for #field_enum {
+            type Output = #field_set;
+
+            fn bitor(self, other: Self) -> #field_set {
+                #field_set::from(self).with(other)
+            }
+        }
+
+        impl ::core::ops::BitOr<#field_enum> for #field_set {
+            type Output = Self;
+
+            fn bitor(self, field: #field_enum) -> Self {
+                self.with(field)
+            }
+        }
+
+        impl ::core::ops::BitOr for #field_set {
+            type Output = Self;
+
+            fn bitor(self, other: Self) -> Self {
+                Self { bits: self.bits | other.bits }
+            }
+        }
+
+        impl ::core::iter::FromIterator<#field_enum> for #field_set {
+            fn from_iter<I: ::core::iter::IntoIterator<Item = #field_enum>>(fields: I) -> Self {
+                fields.into_iter().fold(Self::empty(), Self::with)
+            }
+        }
+
+        impl #impl_generics #name #ty_generics #where_clause {
+            /// Clone this value, resetting the fields of `mask` to their override, or to their
+            /// default value when they have none.
+            #[allow(unused_variables)]
+            #vis fn clone_except(&self, #mask: #field_set) -> Self {
+                Self {
+                    #(#cloned_fields,)*
+                }
+            }
+
+            /// Clone only the fields of `fields`, resetting the other ones as `clone_except` does.
+            #vis fn clone_only(&self, fields: #field_set) -> Self {
+                self.clone_except(fields.complement())
+            }
+        }
+    }
+}
+
+/// The name of the `<Name>Field` variant of a field: `created_at` is `CreatedAt`.
+fn variant_name(member: &Member) -> Ident {
+    let Member::Named(ident) = member else {
+        unreachable!("`mask` on a struct with unnamed fields");
+    };
+    let name = ident.to_string();
+    let name = name.strip_prefix("r#").unwrap_or(&name);
+    let mut variant = String::new();
+    for word in name.split('_').filter(|word| !word.is_empty()) {
+        let mut chars = word.chars();
+        variant.extend(chars.next().map(|first| first.to_ascii_uppercase()));
+        variant.push_str(chars.as_str());
+    }
+    // `_0` would not be an identifier.
+    if !variant.starts_with(|first: char| first.is_alphabetic()) {
+        variant.insert_str(0, "Field");
+    }
+    format_ident!("{}", variant, span = ident.span())
+}
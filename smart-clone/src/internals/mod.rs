@@ -1,13 +1,29 @@
 extern crate proc_macro;
 
-use proc_macro2::TokenStream;
-use quote::quote;
-use syn::{Data, DeriveInput};
+use proc_macro2::{Ident, Span, TokenStream, TokenTree};
+use quote::{format_ident, quote};
+use syn::DeriveInput;
 
-use enum_smart_clone::clone_enum_type;
-use struct_smart_clone::clone_struct_type;
+use ast::{Container, Data};
+use attr::Errors;
+use bound::with_clone_bound;
+use builder_smart_clone::clone_builder;
+use deep_smart_clone::{graph_memo, graph_memo_type};
+use enum_smart_clone::{clone_enum_type, clone_from_enum_type};
+use field_smart_clone::never_cloned_field;
+use mask_smart_clone::clone_mask;
+use projection_smart_clone::clone_projection;
+use struct_smart_clone::{clone_from_struct_type, clone_struct_type};
 
+mod ast;
+mod attr;
+mod bound;
+mod builder_smart_clone;
+mod deep_smart_clone;
 mod enum_smart_clone;
+mod field_smart_clone;
+mod mask_smart_clone;
+mod projection_smart_clone;
 mod struct_smart_clone;
 
 /// Defines if a structure or a field uses its default cloning
@@ -15,30 +31,291 @@ mod struct_smart_clone;
 enum CloneMode {
     Standard,
     Overridden(TokenStream),
+    /// `#[clone(value = ...)]` on an enum variant: the expression can read the fields of the
+    /// variant, bound by name or as `v0, v1...` for unnamed fields.
+    Expression(TokenStream),
+    /// `#[clone(reset)]` on an enum variant: the variant is kept and its fields are reset.
+    Reset,
+    /// `#[clone(with_fields = func)]` on an enum variant: the function is called with the fields.
+    WithFields(syn::Expr),
+    /// The enum variant, or one of its fields, must never be cloned: the expression diverges.
+    Never(TokenStream),
+}
+
+/// The method being generated, which defines how some strategies apply.
+#[derive(Clone, Copy, PartialEq)]
+enum Method<'a> {
+    /// `Clone::clone`.
+    Clone,
+    /// `clone_with_ctx(&self, ctx: &mut Ctx)`, generated with `#[clone(context = Ctx)]`.
+    CloneWithCtx,
+    /// `try_clone(&self) -> Result<Self, E>`, generated by `#[derive(SmartTryClone)]`. The errors
+    /// are boxed along with the name of the failing field when no `#[clone(error = E)]` is given.
+    TryClone { boxed: bool },
+    /// `deep_clone(&self) -> Self`, generated by `#[derive(DeepClone)]`: every field is cloned as
+    /// with `#[clone(deep)]`, unless it has another strategy.
+    DeepClone,
+    /// `clone_graph(&self) -> Self`, generated by `#[derive(GraphClone)]`: as `DeepClone`, but the
+    /// shared nodes are cloned once and the `Weak` pointers are re-pointed at the new nodes.
+    GraphClone,
+    /// `clone_<name>(&self) -> Self`, generated for each `#[clone(profile = "name", ...)]`.
+    Profile(&'a Ident),
+    /// The projection of `#[clone(into = Name)]`, building a `Name` without the excluded fields.
+    Into(&'a Ident),
+}
+
+/// The `ctx` parameter of `clone_with_ctx`, hygienic so that a field of the same name, bound in a
+/// variant pattern, does not shadow it.
+fn context_param() -> Ident {
+    Ident::new("ctx", Span::mixed_site())
+}
+
+/// Checks if the given tokens use `self` as a value (`self.x`, `f(self)`), rather than as a path
+/// prefix (`self::f`).
+fn mentions_self(tokens: &TokenStream) -> bool {
+    let mut tokens = tokens.clone().into_iter().peekable();
+    while let Some(token) = tokens.next() {
+        match token {
+            TokenTree::Ident(ident) if ident == "self" => match tokens.peek() {
+                Some(TokenTree::Punct(punct)) if punct.as_char() == ':' => {}
+                _ => return true,
+            },
+            TokenTree::Group(group) if mentions_self(&group.stream()) => return true,
+            _ => {}
+        }
+    }
+    false
 }
 
 /// Implementation for the #[derive(SmartClone)] macros.
 pub fn smart_clone_derive(input: TokenStream) -> TokenStream {
-    // Parse the input tokens into a syntax tree
-    let input: DeriveInput = syn::parse2(input).expect("Impossible to parse macro SmartClone");
+    derive(input, |container| {
+        // Get the name of the struct
+        let structure_name = container.ident;
+
+        // Carry the generics over to the impl, bounding only the type parameters that need it.
+        let method = match container.attrs.skip_clone {
+            true => Method::CloneWithCtx,
+            false => Method::Clone,
+        };
+        let generics = with_clone_bound(container, method);
+        let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+
+        // Generate the implementation of the Clone trait, unless only `clone_with_ctx` is wanted.
+        let clone_impl = match container.attrs.skip_clone {
+            true => None,
+            false => {
+                let cloned = clone_data(container, Method::Clone);
+                let clone_from = match &container.data {
+                    Data::Struct(_, fields) => clone_from_struct_type(fields),
+                    Data::Enum(variants) => clone_from_enum_type(structure_name, variants),
+                };
+                Some(quote! {
+                    impl #impl_generics Clone for #structure_name #ty_generics #where_clause {
+                        fn clone(&self) -> Self {
+                            #cloned
+                        }
+                        #clone_from
+                    }
+                })
+            }
+        };
+
+        // Generate `clone_with_ctx` when a context is given.
+        let clone_with_ctx_impl = container.attrs.context.as_ref().map(|context| {
+            let vis = container.vis;
+            let cloned = clone_data(container, Method::CloneWithCtx);
+            let ctx = context_param();
+            quote! {
+                impl #impl_generics #structure_name #ty_generics #where_clause {
+                    #[allow(unused_variables)]
+                    #vis fn clone_with_ctx(&self, #ctx: &mut #context) -> Self {
+                        #cloned
+                    }
+                }
+            }
+        });
+
+        // Generate a `clone_<name>` method for each profile.
+        let profile_impls = container.profiles().into_iter().map(|profile| {
+            let method = Method::Profile(profile);
+            let generics = with_clone_bound(container, method);
+            let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+            let vis = container.vis;
+            let method_name = format_ident!("clone_{}", profile);
+            let cloned = clone_data(container, method);
+            quote! {
+                impl #impl_generics #structure_name #ty_generics #where_clause {
+                    #vis fn #method_name(&self) -> Self {
+                        #cloned
+                    }
+                }
+            }
+        });
+
+        // Generate the clone builder when asked for.
+        let builder_impl = match (&container.attrs.builder, &container.data) {
+            (Some(_), Data::Struct(_, fields)) => Some(clone_builder(
+                container,
+                fields,
+                &with_clone_bound(container, Method::Clone),
+            )),
+            _ => None,
+        };
+
+        // Generate `clone_except` and `clone_only` when asked for.
+        let mask_impl = match (&container.attrs.mask, &container.data) {
+            (Some(_), Data::Struct(_, fields)) => Some(clone_mask(
+                container,
+                fields,
+                &with_clone_bound(container, Method::Clone),
+            )),
+            _ => None,
+        };
+
+        // Generate the projection of `#[clone(into = ...)]`.
+        let projection_impl = container
+            .attrs
+            .into
+            .as_ref()
+            .map(|target| clone_projection(container, target));
+
+        quote! {
+            #clone_impl
+            #clone_with_ctx_impl
+            #(#profile_impls)*
+            #builder_impl
+            #mask_impl
+            #projection_impl
+        }
+    })
+}
+
+/// Implementation for the #[derive(SmartTryClone)] macros.
+pub fn smart_try_clone_derive(input: TokenStream) -> TokenStream {
+    derive(input, |container| {
+        let structure_name = container.ident;
+        let vis = container.vis;
 
-    // Get the name of the struct
-    let structure_name = &input.ident;
+        // Errors are converted into the declared type, or boxed.
+        let (error, method) = match &container.attrs.error {
+            Some(error) => (quote! { #error }, Method::TryClone { boxed: false }),
+            None => (
+                quote! { ::std::boxed::Box<dyn ::std::error::Error + ::core::marker::Send + ::core::marker::Sync> },
+                Method::TryClone { boxed: true },
+            ),
+        };
+        let generics = with_clone_bound(container, method);
+        let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+        let cloned = clone_data(container, method);
+        // A struct with a field which must never be cloned is never wrapped in `Ok`.
+        let allow_unreachable = match &container.data {
+            Data::Struct(_, fields) if never_cloned_field(fields, method).is_some() => {
+                Some(quote! { #[allow(unreachable_code)] })
+            }
+            _ => None,
+        };
+
+        quote! {
+            impl #impl_generics #structure_name #ty_generics #where_clause {
+                #allow_unreachable
+                #vis fn try_clone(&self) -> ::core::result::Result<Self, #error> {
+                    ::core::result::Result::Ok(#cloned)
+                }
+            }
+        }
+    })
+}
+
+/// Implementation for the #[derive(DeepClone)] macros.
+pub fn smart_deep_clone_derive(input: TokenStream) -> TokenStream {
+    derive(input, |container| {
+        let structure_name = container.ident;
+        let vis = container.vis;
+        let generics = with_clone_bound(container, Method::DeepClone);
+        let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+        let cloned = clone_data(container, Method::DeepClone);
+
+        quote! {
+            impl #impl_generics #structure_name #ty_generics #where_clause {
+                #vis fn deep_clone(&self) -> Self {
+                    #cloned
+                }
+            }
+        }
+    })
+}
+
+/// Implementation for the #[derive(GraphClone)] macros.
+pub fn smart_graph_clone_derive(input: TokenStream) -> TokenStream {
+    derive(input, |container| {
+        let structure_name = container.ident;
+        let vis = container.vis;
+        let generics = with_clone_bound(container, Method::GraphClone);
+        let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+        let cloned = clone_data(container, Method::GraphClone);
+        let memo = graph_memo();
+        let memo_type = graph_memo_type();
+
+        quote! {
+            impl #impl_generics #structure_name #ty_generics #where_clause {
+                #vis fn clone_graph(&self) -> Self {
+                    self.clone_graph_in(&mut ::std::collections::HashMap::new())
+                }
+                #[doc(hidden)]
+                #[allow(unused_variables)]
+                #vis fn clone_graph_in(&self, #memo: &mut #memo_type) -> Self {
+                    #cloned
+                }
+            }
+        }
+    })
+}
 
-    // Process the data associated with the #[derive(SmartClone)].
-    let cloned = match input.data {
-        Data::Struct(data_struct) => clone_struct_type(structure_name, data_struct),
-        Data::Enum(enum_struct) => clone_enum_type(structure_name, enum_struct),
-        Data::Union(_) => return quote! { compile_error!("Cannot use SmartClone on union types.") },
+/// Parse the input and read its `#[clone...]` attributes, reporting all the errors at once, then
+/// generate the code.
+fn derive(input: TokenStream, generate: impl FnOnce(&Container) -> TokenStream) -> TokenStream {
+    // Parse the input tokens into a syntax tree
+    let input: DeriveInput = match syn::parse2(input) {
+        Ok(input) => input,
+        Err(error) => return error.to_compile_error(),
     };
 
-    // Generate the implementation of the Clone trait
-    quote! {
-        impl Clone for #structure_name {
-            fn clone(&self) -> Self {
-                #cloned
+    // Read the `#[clone...]` attributes, reporting all the errors at once.
+    let mut errors = Errors::default();
+    let container = Container::from_ast(&input, &mut errors);
+    match (container, errors.finish()) {
+        (Some(container), Ok(deprecated)) => {
+            let generated = generate(&container);
+            let warnings = deprecated.into_iter().map(deprecation_warning);
+            quote! {
+                #generated
+                #(#warnings)*
             }
         }
+        (_, Err(error)) => error.to_compile_error(),
+        (None, Ok(_)) => unreachable!("a container is always read unless an error is reported"),
+    }
+}
+
+/// Warn about the deprecated `#[clone(expr)]` syntax at the given span, through the use of a
+/// `#[deprecated]` constant, as a derive cannot emit warnings by itself.
+fn deprecation_warning(span: Span) -> TokenStream {
+    let constant = Ident::new("CLONE_WITHOUT_VALUE", span);
+    quote! {
+        const _: () = {
+            #[deprecated(note = "`#[clone(expr)]` is deprecated, use `#[clone(value = expr)]`")]
+            const #constant: () = ();
+            #constant
+        };
+    }
+}
+
+/// Clone the data of a struct or an enum within the generated `method`.
+fn clone_data(container: &Container, method: Method) -> TokenStream {
+    match &container.data {
+        Data::Struct(style, fields) => clone_struct_type(container.ident, *style, fields, method),
+        Data::Enum(variants) => clone_enum_type(container.ident, variants, method),
     }
 }
 
@@ -46,7 +323,10 @@ pub fn smart_clone_derive(input: TokenStream) -> TokenStream {
 mod tests {
     use quote::quote;
 
-    use crate::internals::smart_clone_derive;
+    use crate::internals::{
+        smart_clone_derive, smart_deep_clone_derive, smart_graph_clone_derive,
+        smart_try_clone_derive,
+    };
 
     #[test]
     fn test_struct_with_no_clone_attr() {
@@ -61,10 +341,14 @@ mod tests {
             impl Clone for Bar {
                 fn clone(&self) -> Self {
                     Self {
-                        x: self.x.clone(),
-                        y: self.y.clone(),
+                        x: ::core::clone::Clone::clone(&self.x),
+                        y: ::core::clone::Clone::clone(&self.y),
                     }
                 }
+                fn clone_from(&mut self, source: &Self) {
+                    self.x.clone_from(&source.x);
+                    self.y.clone_from(&source.y);
+                }
             }
         };
         let result = smart_clone_derive(input).to_string();
@@ -94,7 +378,16 @@ mod tests {
                         b: (u8, u8),
                    }
                 }
+                fn clone_from(&mut self, source: &Self) {
+                    self.a = foobar;
+                    self.b = (u8, u8);
+                }
             }
+            const _: () = {
+                #[deprecated(note = "`#[clone(expr)]` is deprecated, use `#[clone(value = expr)]`")]
+                const CLONE_WITHOUT_VALUE: () = ();
+                CLONE_WITHOUT_VALUE
+            };
         };
 
         let result = smart_clone_derive(input).to_string();
@@ -121,7 +414,15 @@ mod tests {
                         z: custom_clone_expr,
                     }
                 }
+                fn clone_from(&mut self, source: &Self) {
+                    self.z = custom_clone_expr;
+                }
             }
+            const _: () = {
+                #[deprecated(note = "`#[clone(expr)]` is deprecated, use `#[clone(value = expr)]`")]
+                const CLONE_WITHOUT_VALUE: () = ();
+                CLONE_WITHOUT_VALUE
+            };
         };
         let result = smart_clone_derive(input).to_string();
         assert_eq!(
@@ -150,6 +451,10 @@ mod tests {
                         z: Foo::bar(&self.z),
                     }
                 }
+                fn clone_from(&mut self, source: &Self) {
+                    self.a = youpi(&source.a);
+                    self.z = Foo::bar(&source.z);
+                }
             }
         };
         let result = smart_clone_derive(input).to_string();
@@ -176,6 +481,9 @@ mod tests {
                         a: Default::default(),
                     }
                 }
+                fn clone_from(&mut self, source: &Self) {
+                    self.a = Default::default();
+                }
             }
         };
         let result = smart_clone_derive(input).to_string();
@@ -201,8 +509,21 @@ mod tests {
                 fn clone(&self) -> Self {
                     match self {
                         SimpleEnum::A => SimpleEnum::A,
-                        SimpleEnum::B(v0, v1) => SimpleEnum::B(v0.clone(), v1.clone()),
-                        SimpleEnum::C { x, y } => SimpleEnum::C { x: x.clone(), y: y.clone() },
+                        SimpleEnum::B(v0, v1) => SimpleEnum::B(::core::clone::Clone::clone(v0), ::core::clone::Clone::clone(v1)),
+                        SimpleEnum::C { x, y } => SimpleEnum::C { x: ::core::clone::Clone::clone(x), y: ::core::clone::Clone::clone(y) },
+                    }
+                }
+                fn clone_from(&mut self, source: &Self) {
+                    match (&mut *self, source) {
+                        (SimpleEnum::B(self_v0, self_v1), SimpleEnum::B(v0, v1)) => {
+                            self_v0.clone_from(v0);
+                            self_v1.clone_from(v1);
+                        },
+                        (SimpleEnum::C { x: self_x, y: self_y }, SimpleEnum::C { x, y }) => {
+                            self_x.clone_from(x);
+                            self_y.clone_from(y);
+                        },
+                        _ => *self = source.clone(),
                     }
                 }
             }
@@ -233,7 +554,9 @@ mod tests {
                 fn clone(&self) -> Self {
                     match self {
                         CustomCloneEnum::A => CustomCloneEnum::A,
-                        CustomCloneEnum::B(..) => CustomCloneEnum::B(8, 12),
+                        #[allow(unused_variables)]
+                        CustomCloneEnum::B(v0, v1) => CustomCloneEnum::B(8, 12),
+                        #[allow(unused_variables)]
                         CustomCloneEnum::C { x, y } => custom_clone_expr,
                         CustomCloneEnum::D => Try::func(self),
                         CustomCloneEnum::E => CustomCloneEnum::E,
@@ -241,11 +564,100 @@ mod tests {
                     }
                 }
             }
+            const _: () = {
+                #[deprecated(note = "`#[clone(expr)]` is deprecated, use `#[clone(value = expr)]`")]
+                const CLONE_WITHOUT_VALUE: () = ();
+                CLONE_WITHOUT_VALUE
+            };
         };
         let result = smart_clone_derive(input).to_string();
         assert_eq!(result, output.to_string(), "Enum various tags: {}", result);
     }
 
+    #[test]
+    fn test_enum_with_field_clone_attr() {
+        let input = quote! {
+            enum Machine {
+                A(u8, #[clone(default)] Vec<u8>, #[clone(clone_with = "reset")] Handle),
+                B {
+                    x: u8,
+                    #[clone(default)]
+                    cache: Vec<u8>,
+                    #[clone = 3]
+                    y: u8,
+                },
+                #[clone(default)]
+                C { #[clone(clone_with = "ignored")] z: u8 },
+            }
+        };
+        let output = quote! {
+            impl Clone for Machine {
+                fn clone(&self) -> Self {
+                    match self {
+                        Machine::A(v0, _, v2) => Machine::A(::core::clone::Clone::clone(v0), Default::default(), reset(v2)),
+                        Machine::B { x, .. } => Machine::B { x: ::core::clone::Clone::clone(x), cache: Default::default(), y: 3 },
+                        Machine::C { .. } => Default::default(),
+                    }
+                }
+                fn clone_from(&mut self, source: &Self) {
+                    match (&mut *self, source) {
+                        (Machine::A(self_v0, self_v1, self_v2), Machine::A(v0, _, v2)) => {
+                            self_v0.clone_from(v0);
+                            *self_v1 = Default::default();
+                            *self_v2 = reset(v2);
+                        },
+                        (Machine::B { x: self_x, cache: self_cache, y: self_y }, Machine::B { x, .. }) => {
+                            self_x.clone_from(x);
+                            *self_cache = Default::default();
+                            *self_y = 3;
+                        },
+                        _ => *self = source.clone(),
+                    }
+                }
+            }
+        };
+        let result = smart_clone_derive(input).to_string();
+        assert_eq!(
+            result,
+            output.to_string(),
+            "Enum with field tags: {}",
+            result
+        );
+    }
+
+    #[test]
+    fn test_clone_from_reading_self() {
+        let input = quote! {
+            struct Counter {
+                name: String,
+                #[clone(self.generation + 1)]
+                generation: u32,
+            }
+        };
+        let output = quote! {
+            impl Clone for Counter {
+                fn clone(&self) -> Self {
+                    Self {
+                        name: ::core::clone::Clone::clone(&self.name),
+                        generation: self.generation + 1,
+                    }
+                }
+            }
+            const _: () = {
+                #[deprecated(note = "`#[clone(expr)]` is deprecated, use `#[clone(value = expr)]`")]
+                const CLONE_WITHOUT_VALUE: () = ();
+                CLONE_WITHOUT_VALUE
+            };
+        };
+        let result = smart_clone_derive(input).to_string();
+        assert_eq!(
+            result,
+            output.to_string(),
+            "Default clone_from when reading self: {}",
+            result
+        );
+    }
+
     #[test]
     fn test_unit_type() {
         let input = quote! {
@@ -288,20 +700,175 @@ mod tests {
             impl Clone for UnnamedStruct {
                 fn clone (& self) -> Self {
                     UnnamedStruct {
-                        0: self.0.clone(),
+                        0: ::core::clone::Clone::clone(&self.0),
                         1: 3,
-                        2: self.2.clone(),
+                        2: ::core::clone::Clone::clone(&self.2),
                         3: Default::default(),
                         4: custom_value,
                         5: wow(&self.5),
                     }
                 }
+                fn clone_from(&mut self, source: &Self) {
+                    self.0.clone_from(&source.0);
+                    self.1 = 3;
+                    self.2.clone_from(&source.2);
+                    self.3 = Default::default();
+                    self.4 = custom_value;
+                    self.5 = wow(&source.5);
+                }
             }
+            const _: () = {
+                #[deprecated(note = "`#[clone(expr)]` is deprecated, use `#[clone(value = expr)]`")]
+                const CLONE_WITHOUT_VALUE: () = ();
+                CLONE_WITHOUT_VALUE
+            };
         };
         let result = smart_clone_derive(input).to_string();
         assert_eq!(result, output.to_string(), "Unnamed structures: {}", result);
     }
 
+    #[test]
+    fn test_generic_struct() {
+        let input = quote! {
+            struct Cache<'a, K, V, M: Marker, const N: usize> where K: Hash {
+                keys: Vec<K>,
+                #[clone(default)]
+                values: Vec<V>,
+                label: &'a str,
+                marker: PhantomData<M>,
+                buffer: [u8; N],
+            }
+        };
+        let output = quote! {
+            impl<'a, K, V, M: Marker, const N: usize> Clone for Cache<'a, K, V, M, N>
+            where
+                K: Hash,
+                K: Clone
+            {
+                fn clone(&self) -> Self {
+                    Self {
+                        keys: ::core::clone::Clone::clone(&self.keys),
+                        values: Default::default(),
+                        label: ::core::clone::Clone::clone(&self.label),
+                        marker: ::core::clone::Clone::clone(&self.marker),
+                        buffer: ::core::clone::Clone::clone(&self.buffer),
+                    }
+                }
+                fn clone_from(&mut self, source: &Self) {
+                    self.keys.clone_from(&source.keys);
+                    self.values = Default::default();
+                    self.label.clone_from(&source.label);
+                    self.marker.clone_from(&source.marker);
+                    self.buffer.clone_from(&source.buffer);
+                }
+            }
+        };
+        let result = smart_clone_derive(input).to_string();
+        assert_eq!(result, output.to_string(), "Generic struct: {}", result);
+    }
+
+    #[test]
+    fn test_generic_enum() {
+        let input = quote! {
+            enum Msg<'a, T, U> {
+                Text(&'a str),
+                Payload { value: Option<T> },
+                #[clone(default)]
+                Other(U),
+            }
+        };
+        let output = quote! {
+            impl<'a, T, U> Clone for Msg<'a, T, U>
+            where
+                T: Clone
+            {
+                fn clone(&self) -> Self {
+                    match self {
+                        Msg::Text(v0) => Msg::Text(::core::clone::Clone::clone(v0)),
+                        Msg::Payload { value } => Msg::Payload { value: ::core::clone::Clone::clone(value) },
+                        Msg::Other(..) => Default::default(),
+                    }
+                }
+                fn clone_from(&mut self, source: &Self) {
+                    match (&mut *self, source) {
+                        (Msg::Text(self_v0), Msg::Text(v0)) => {
+                            self_v0.clone_from(v0);
+                        },
+                        (Msg::Payload { value: self_value }, Msg::Payload { value }) => {
+                            self_value.clone_from(value);
+                        },
+                        _ => *self = source.clone(),
+                    }
+                }
+            }
+        };
+        let result = smart_clone_derive(input).to_string();
+        assert_eq!(result, output.to_string(), "Generic enum: {}", result);
+    }
+
+    #[test]
+    fn test_container_bound() {
+        let input = quote! {
+            #[clone(bound = "T: Iterator, T::Item: Clone")]
+            struct Wrapper<T: Iterator> {
+                items: Vec<T::Item>,
+            }
+        };
+        let output = quote! {
+            impl<T: Iterator> Clone for Wrapper<T>
+            where
+                T: Iterator,
+                T::Item: Clone
+            {
+                fn clone(&self) -> Self {
+                    Self {
+                        items: ::core::clone::Clone::clone(&self.items),
+                    }
+                }
+                fn clone_from(&mut self, source: &Self) {
+                    self.items.clone_from(&source.items);
+                }
+            }
+        };
+        let result = smart_clone_derive(input).to_string();
+        assert_eq!(result, output.to_string(), "Container bound: {}", result);
+    }
+
+    #[test]
+    fn test_field_bound() {
+        let input = quote! {
+            struct Shared<T, U, V> {
+                #[clone(bound = "")]
+                shared: Arc<T>,
+                #[clone(default, bound = "U: Default")]
+                reset: U,
+                owned: V,
+            }
+        };
+        let output = quote! {
+            impl<T, U, V> Clone for Shared<T, U, V>
+            where
+                V: Clone,
+                U: Default
+            {
+                fn clone(&self) -> Self {
+                    Self {
+                        shared: ::core::clone::Clone::clone(&self.shared),
+                        reset: Default::default(),
+                        owned: ::core::clone::Clone::clone(&self.owned),
+                    }
+                }
+                fn clone_from(&mut self, source: &Self) {
+                    self.shared.clone_from(&source.shared);
+                    self.reset = Default::default();
+                    self.owned.clone_from(&source.owned);
+                }
+            }
+        };
+        let result = smart_clone_derive(input).to_string();
+        assert_eq!(result, output.to_string(), "Field bound: {}", result);
+    }
+
     #[test]
     fn test_unsupported_types() {
         let input = quote! {
@@ -311,7 +878,7 @@ mod tests {
             }
         };
         let output = quote! {
-            compile_error ! ("Cannot use SmartClone on union types.")
+            ::core::compile_error! { "Cannot use SmartClone on union types." }
         };
         let result = smart_clone_derive(input).to_string();
         assert_eq!(
@@ -321,4 +888,2459 @@ mod tests {
             result
         );
     }
+
+    #[test]
+    fn test_invalid_attributes() {
+        let input = quote! {
+            struct Invalid {
+                #[clone(clone_with = 42)]
+                a: Vec<i32>,
+                #[clone(clone_with = "not a path!!")]
+                b: Vec<i32>,
+                #[clone(1 +)]
+                c: i32,
+                #[clone(bound = "T:: Clone")]
+                d: i32,
+            }
+        };
+        let output = quote! {
+            ::core::compile_error! { "expected a function path or a closure" }
+            ::core::compile_error! { "unexpected token" }
+            ::core::compile_error! { "unexpected end of input, expected an expression" }
+            ::core::compile_error! { "expected `:`" }
+        };
+        let result = smart_clone_derive(input).to_string();
+        assert_eq!(
+            result,
+            output.to_string(),
+            "Invalid attributes error: {}",
+            result
+        );
+    }
+
+    #[test]
+    fn test_invalid_container_attributes() {
+        let input = quote! {
+            #[clone = 3]
+            enum Invalid {
+                #[clone(with = 42)]
+                A,
+            }
+        };
+        let output = quote! {
+            ::core::compile_error! { "expected `#[clone(...)]` options on a struct or an enum" }
+            ::core::compile_error! { "expected a function path or a closure" }
+        };
+        let result = smart_clone_derive(input).to_string();
+        assert_eq!(
+            result,
+            output.to_string(),
+            "Invalid container attributes error: {}",
+            result
+        );
+    }
+
+    #[test]
+    fn test_value_option() {
+        let input = quote! {
+            struct Foo {
+                #[clone(value = 12)]
+                a: u32,
+                #[clone(value = Some(default))]
+                b: Option<u32>,
+                #[clone(a == b)]
+                c: bool,
+            }
+        };
+        let output = quote! {
+            impl Clone for Foo {
+                fn clone(&self) -> Self {
+                    Self {
+                        a: 12,
+                        b: Some(default),
+                        c: a == b,
+                    }
+                }
+                fn clone_from(&mut self, source: &Self) {
+                    self.a = 12;
+                    self.b = Some(default);
+                    self.c = a == b;
+                }
+            }
+            const _: () = {
+                #[deprecated(note = "`#[clone(expr)]` is deprecated, use `#[clone(value = expr)]`")]
+                const CLONE_WITHOUT_VALUE: () = ();
+                CLONE_WITHOUT_VALUE
+            };
+        };
+        let result = smart_clone_derive(input).to_string();
+        assert_eq!(
+            result,
+            output.to_string(),
+            "Impl with #[clone(value = ...)] tag: {}",
+            result
+        );
+    }
+
+    #[test]
+    fn test_unknown_options() {
+        let input = quote! {
+            #[clone(bund = "T: Clone")]
+            struct Typo {
+                #[clone(defualt)]
+                a: Vec<i32>,
+                #[clone(clone_wth = "f")]
+                b: Vec<i32>,
+                #[clone(default, other = 3)]
+                c: Vec<i32>,
+            }
+        };
+        let output = quote! {
+            ::core::compile_error! { "unknown clone option `bund`, did you mean `bound`?" }
+            ::core::compile_error! { "unknown clone option `defualt`, did you mean `default`?" }
+            ::core::compile_error! { "unknown clone option `clone_wth`, did you mean `clone_with`?" }
+            ::core::compile_error! { "unknown clone option `other`, expected one of: `value`, `default`, `with`, `clone_with`, `with_parent`, `with_ctx`, `try_with`, `deep`, `set`, `panic`, `unreachable`, `forbid`, `map`, `uses`, `keep`, `exclude`, `bound`, `profile`" }
+        };
+        let result = smart_clone_derive(input).to_string();
+        assert_eq!(result, output.to_string(), "Unknown options: {}", result);
+    }
+
+    #[test]
+    fn test_merged_attributes() {
+        let input = quote! {
+            struct Merged<T> {
+                #[clone(bound = "T: Default")]
+                #[clone]
+                #[clone(default)]
+                a: T,
+            }
+        };
+        let output = quote! {
+            impl<T> Clone for Merged<T>
+            where
+                T: Default
+            {
+                fn clone(&self) -> Self {
+                    Self {
+                        a: Default::default(),
+                    }
+                }
+                fn clone_from(&mut self, source: &Self) {
+                    self.a = Default::default();
+                }
+            }
+        };
+        let result = smart_clone_derive(input).to_string();
+        assert_eq!(result, output.to_string(), "Merged attributes: {}", result);
+    }
+
+    #[test]
+    fn test_conflicting_attributes() {
+        let input = quote! {
+            #[clone(bound = "")]
+            #[clone(bound = "T: Clone")]
+            enum Conflict<T> {
+                #[clone(default, clone_with = "f")]
+                A(T),
+                #[clone(default)]
+                #[clone = 3]
+                B,
+            }
+        };
+        let output = quote! {
+            ::core::compile_error! { "duplicate clone option `bound`" }
+            ::core::compile_error! { "`bound` is first set here" }
+            ::core::compile_error! { "conflicting clone options `default` and `clone_with`" }
+            ::core::compile_error! { "`default` is first set here" }
+            ::core::compile_error! { "conflicting clone options `default` and `value`" }
+            ::core::compile_error! { "`default` is first set here" }
+        };
+        let result = smart_clone_derive(input).to_string();
+        assert_eq!(
+            result,
+            output.to_string(),
+            "Conflicting attributes: {}",
+            result
+        );
+    }
+
+    #[test]
+    fn test_with_path_and_closure() {
+        let input = quote! {
+            enum Items {
+                A(#[clone(with = helpers::reset::<u32>)] Vec<u32>),
+                B {
+                    #[clone(with = |v: &Vec<u32>| v.iter().rev().cloned().collect())]
+                    v: Vec<u32>,
+                },
+                #[clone(with = Self::fallback)]
+                C,
+            }
+        };
+        let output = quote! {
+            impl Clone for Items {
+                fn clone(&self) -> Self {
+                    match self {
+                        Items::A(v0) => Items::A(helpers::reset::<u32>(v0)),
+                        Items::B { v } => Items::B { v: (|v: &Vec<u32>| v.iter().rev().cloned().collect())(v) },
+                        Items::C => Self::fallback(self),
+                    }
+                }
+                fn clone_from(&mut self, source: &Self) {
+                    match (&mut *self, source) {
+                        (Items::A(self_v0), Items::A(v0)) => {
+                            *self_v0 = helpers::reset::<u32>(v0);
+                        },
+                        (Items::B { v: self_v }, Items::B { v }) => {
+                            *self_v = (|v: &Vec<u32>| v.iter().rev().cloned().collect())(v);
+                        },
+                        _ => *self = source.clone(),
+                    }
+                }
+            }
+        };
+        let result = smart_clone_derive(input).to_string();
+        assert_eq!(
+            result,
+            output.to_string(),
+            "Impl with #[clone(with = ...)] tag: {}",
+            result
+        );
+    }
+
+    #[test]
+    fn test_with_parent() {
+        let input = quote! {
+            struct Node {
+                id: u32,
+                #[clone(with_parent = Self::derive_label)]
+                label: String,
+                #[clone(value = self.generation + 1)]
+                generation: u32,
+            }
+        };
+        let output = quote! {
+            impl Clone for Node {
+                fn clone(&self) -> Self {
+                    Self {
+                        id: ::core::clone::Clone::clone(&self.id),
+                        label: Self::derive_label(&self.label, self),
+                        generation: self.generation + 1,
+                    }
+                }
+            }
+        };
+        let result = smart_clone_derive(input).to_string();
+        assert_eq!(
+            result,
+            output.to_string(),
+            "Impl with #[clone(with_parent = ...)] tag: {}",
+            result
+        );
+    }
+
+    #[test]
+    fn test_uses() {
+        let input = quote! {
+            struct Packet {
+                #[clone(default)]
+                id: u32,
+                payload: Vec<u8>,
+                #[clone(value = crc(&payload), uses(payload))]
+                checksum: u32,
+            }
+        };
+        let output = quote! {
+            impl Clone for Packet {
+                fn clone(&self) -> Self {
+                    {
+                        let id: u32 = Default::default();
+                        let payload: Vec<u8> = ::core::clone::Clone::clone(&self.payload);
+                        let checksum: u32 = crc(&payload);
+                        Self { id, payload, checksum }
+                    }
+                }
+            }
+        };
+        let result = smart_clone_derive(input).to_string();
+        assert_eq!(
+            result,
+            output.to_string(),
+            "Impl with #[clone(uses(...))] tag: {}",
+            result
+        );
+    }
+
+    #[test]
+    fn test_invalid_uses() {
+        let input = quote! {
+            struct Invalid {
+                #[clone(value = next + 1, uses(next))]
+                previous: u32,
+                next: u32,
+                #[clone(value = 0, uses(missing))]
+                other: u32,
+            }
+        };
+        let output = quote! {
+            ::core::compile_error! { "`uses` can only read fields declared before this one: fields are cloned in declaration order" }
+            ::core::compile_error! { "unknown field in `uses`" }
+        };
+        let result = smart_clone_derive(input).to_string();
+        assert_eq!(result, output.to_string(), "Invalid uses: {}", result);
+
+        let input = quote! {
+            enum Invalid {
+                A(#[clone(value = 1, uses(x))] u32),
+                #[clone(with_parent = f)]
+                B,
+            }
+        };
+        let output = quote! {
+            ::core::compile_error! { "`uses` is only supported on the fields of a struct" }
+            ::core::compile_error! { "unknown clone option `with_parent`, expected one of: `value`, `default`, `with`, `clone_with`, `with_fields`, `with_ctx`, `try_with`, `to`, `reset`, `panic`, `unreachable`, `forbid`, `map`, `bound`, `profile`" }
+        };
+        let result = smart_clone_derive(input).to_string();
+        assert_eq!(result, output.to_string(), "Invalid uses: {}", result);
+    }
+
+    #[test]
+    fn test_context() {
+        let input = quote! {
+            #[clone(context = IdAllocator)]
+            pub struct Node {
+                #[clone(with_ctx = IdAllocator::next)]
+                id: u64,
+                #[clone(default)]
+                label: String,
+                children: Vec<u64>,
+            }
+        };
+        let output = quote! {
+            impl Clone for Node {
+                fn clone(&self) -> Self {
+                    Self {
+                        id: ::core::clone::Clone::clone(&self.id),
+                        label: Default::default(),
+                        children: ::core::clone::Clone::clone(&self.children),
+                    }
+                }
+                fn clone_from(&mut self, source: &Self) {
+                    self.id.clone_from(&source.id);
+                    self.label = Default::default();
+                    self.children.clone_from(&source.children);
+                }
+            }
+            impl Node {
+                #[allow(unused_variables)]
+                pub fn clone_with_ctx(&self, ctx: &mut IdAllocator) -> Self {
+                    Self {
+                        id: IdAllocator::next(&self.id, &mut *ctx),
+                        label: Default::default(),
+                        children: ::core::clone::Clone::clone(&self.children),
+                    }
+                }
+            }
+        };
+        let result = smart_clone_derive(input).to_string();
+        assert_eq!(
+            result,
+            output.to_string(),
+            "Impl with #[clone(context = ...)] tag: {}",
+            result
+        );
+    }
+
+    #[test]
+    fn test_context_skip_clone() {
+        let input = quote! {
+            #[clone(context = Interner, skip_clone)]
+            enum Symbol<T> {
+                #[clone(with_ctx = Self::intern)]
+                Name(String),
+                Tagged(T, #[clone(with_ctx = |tag: &u32, ctx: &mut Interner| ctx.retag(*tag))] u32),
+                Empty,
+            }
+        };
+        let output = quote! {
+            impl<T> Symbol<T> where T: Clone {
+                #[allow(unused_variables)]
+                fn clone_with_ctx(&self, ctx: &mut Interner) -> Self {
+                    match self {
+                        Symbol::Name(..) => Self::intern(self, &mut *ctx),
+                        Symbol::Tagged(v0, v1) => Symbol::Tagged(::core::clone::Clone::clone(v0), (|tag: &u32, ctx: &mut Interner| ctx.retag(*tag))(v1, &mut *ctx)),
+                        Symbol::Empty => Symbol::Empty,
+                    }
+                }
+            }
+        };
+        let result = smart_clone_derive(input).to_string();
+        assert_eq!(
+            result,
+            output.to_string(),
+            "Impl with #[clone(skip_clone)] tag: {}",
+            result
+        );
+    }
+
+    #[test]
+    fn test_invalid_context() {
+        let input = quote! {
+            #[clone(skip_clone)]
+            struct Invalid {
+                #[clone(with_ctx = Ctx::next)]
+                id: u64,
+            }
+        };
+        let output = quote! {
+            ::core::compile_error! { "`skip_clone` requires a `context`: nothing would be generated" }
+            ::core::compile_error! { "`with_ctx` requires a `#[clone(context = ...)]` on the struct or enum" }
+        };
+        let result = smart_clone_derive(input).to_string();
+        assert_eq!(result, output.to_string(), "Invalid context: {}", result);
+    }
+
+    #[test]
+    fn test_try_clone() {
+        let input = quote! {
+            #[clone(error = std::io::Error)]
+            pub struct Log<T> {
+                name: String,
+                #[clone(try_with = File::try_clone)]
+                file: File,
+                #[clone(default)]
+                buffer: Vec<T>,
+            }
+        };
+        let output = quote! {
+            impl<T> Log<T> {
+                pub fn try_clone(&self) -> ::core::result::Result<Self, std::io::Error> {
+                    ::core::result::Result::Ok(Self {
+                        name: ::core::clone::Clone::clone(&self.name),
+                        file: File::try_clone(&self.file)?,
+                        buffer: Default::default(),
+                    })
+                }
+            }
+        };
+        let result = smart_try_clone_derive(input).to_string();
+        assert_eq!(
+            result,
+            output.to_string(),
+            "Impl with #[clone(try_with = ...)] tag: {}",
+            result
+        );
+    }
+
+    #[test]
+    fn test_try_clone_boxed_error() {
+        let input = quote! {
+            enum Handle<T> {
+                #[clone(try_with = Self::reopen)]
+                Ffi(u32),
+                Stream(#[clone(try_with = TcpStream::try_clone)] TcpStream, T),
+            }
+        };
+        let output = quote! {
+            impl<T> Handle<T> where T: Clone {
+                fn try_clone(&self) -> ::core::result::Result<Self, ::std::boxed::Box<dyn ::std::error::Error + ::core::marker::Send + ::core::marker::Sync> > {
+                    ::core::result::Result::Ok(match self {
+                        Handle::Ffi(..) => Self::reopen(self).map_err(|error| -> ::std::boxed::Box<dyn ::std::error::Error + ::core::marker::Send + ::core::marker::Sync> {
+                            ::std::format!("failed to clone variant `Ffi`: {}", error).into()
+                        })?,
+                        Handle::Stream(v0, v1) => Handle::Stream(
+                            TcpStream::try_clone(v0).map_err(|error| -> ::std::boxed::Box<dyn ::std::error::Error + ::core::marker::Send + ::core::marker::Sync> {
+                                ::std::format!("failed to clone field `0`: {}", error).into()
+                            })?,
+                            ::core::clone::Clone::clone(v1)
+                        ),
+                    })
+                }
+            }
+        };
+        let result = smart_try_clone_derive(input).to_string();
+        assert_eq!(
+            result,
+            output.to_string(),
+            "Impl with boxed try_clone errors: {}",
+            result
+        );
+
+        // `Clone` clones `try_with` fields as usual.
+        let input = quote! {
+            struct Shared {
+                #[clone(try_with = Arc::try_clone)]
+                handle: Arc<u32>,
+            }
+        };
+        let output = quote! {
+            impl Clone for Shared {
+                fn clone(&self) -> Self {
+                    Self {
+                        handle: ::core::clone::Clone::clone(&self.handle),
+                    }
+                }
+                fn clone_from(&mut self, source: &Self) {
+                    self.handle.clone_from(&source.handle);
+                }
+            }
+        };
+        let result = smart_clone_derive(input).to_string();
+        assert_eq!(
+            result,
+            output.to_string(),
+            "Clone with #[clone(try_with = ...)] tag: {}",
+            result
+        );
+    }
+
+    #[test]
+    fn test_deep_field() {
+        let input = quote! {
+            struct Document {
+                name: String,
+                #[clone(deep)]
+                config: Option<Arc<RwLock<Config>>>,
+            }
+        };
+        let output = quote! {
+            impl Clone for Document {
+                fn clone(&self) -> Self {
+                    Self {
+                        name: ::core::clone::Clone::clone(&self.name),
+                        config: (&self.config).as_ref().map(|value| <Arc<RwLock<Config> > >::new(<RwLock<Config> >::new({
+                            #[allow(dead_code)]
+                            trait DeepCloneFallback: Clone {
+                                fn deep_clone(&self) -> Self {
+                                    self.clone()
+                                }
+                            }
+                            impl<T: Clone> DeepCloneFallback for T {}
+                            (&*(&**value).read().unwrap_or_else(|poisoned| poisoned.into_inner())).deep_clone()
+                        }))),
+                    }
+                }
+                fn clone_from(&mut self, source: &Self) {
+                    self.name.clone_from(&source.name);
+                    self.config = (&source.config).as_ref().map(|value| <Arc<RwLock<Config> > >::new(<RwLock<Config> >::new({
+                        #[allow(dead_code)]
+                        trait DeepCloneFallback: Clone {
+                            fn deep_clone(&self) -> Self {
+                                self.clone()
+                            }
+                        }
+                        impl<T: Clone> DeepCloneFallback for T {}
+                        (&*(&**value).read().unwrap_or_else(|poisoned| poisoned.into_inner())).deep_clone()
+                    })));
+                }
+            }
+        };
+        let result = smart_clone_derive(input).to_string();
+        assert_eq!(
+            result,
+            output.to_string(),
+            "Impl with #[clone(deep)] tag: {}",
+            result
+        );
+    }
+
+    #[test]
+    fn test_deep_clone_derive() {
+        let input = quote! {
+            pub enum Tree<T> {
+                Leaf(T),
+                Node {
+                    children: Vec<Rc<RefCell<Tree<T>>>>,
+                    #[clone(default)]
+                    cache: Vec<u8>,
+                },
+            }
+        };
+        let leaf = |reference| {
+            quote! {
+                {
+                    #[allow(dead_code)]
+                    trait DeepCloneFallback: Clone {
+                        fn deep_clone(&self) -> Self {
+                            self.clone()
+                        }
+                    }
+                    impl<T: Clone> DeepCloneFallback for T {}
+                    (#reference).deep_clone()
+                }
+            }
+        };
+        let v0 = leaf(quote! { v0 });
+        let tree = leaf(quote! { &*(&**value).borrow() });
+        let output = quote! {
+            impl<T> Tree<T> where T: Clone {
+                pub fn deep_clone(&self) -> Self {
+                    match self {
+                        Tree::Leaf(v0) => Tree::Leaf(#v0),
+                        Tree::Node { children, .. } => Tree::Node {
+                            children: (children).iter().map(|value| <Rc<RefCell<Tree<T> > > >::new(<RefCell<Tree<T> > >::new(#tree))).collect::<Vec<Rc<RefCell<Tree<T> > > > >(),
+                            cache: Default::default()
+                        },
+                    }
+                }
+            }
+        };
+        let result = smart_deep_clone_derive(input).to_string();
+        assert_eq!(
+            result,
+            output.to_string(),
+            "Impl of #[derive(DeepClone)]: {}",
+            result
+        );
+    }
+
+    #[test]
+    fn test_graph_clone_derive() {
+        let input = quote! {
+            struct Node<T> {
+                value: T,
+                parent: Weak<Node<T>>,
+                children: Vec<Rc<Node<T>>>,
+            }
+        };
+        let memo_type = quote! {
+            ::std::collections::HashMap<usize, ::std::boxed::Box<dyn ::std::any::Any>>
+        };
+        let leaf = |reference| {
+            quote! {
+                {
+                    #[allow(dead_code)]
+                    trait GraphCloneFallback: Clone {
+                        fn clone_graph_in(&self, _memo: &mut #memo_type) -> Self {
+                            self.clone()
+                        }
+                    }
+                    impl<T: Clone> GraphCloneFallback for T {}
+                    (#reference).clone_graph_in(&mut *memo)
+                }
+            }
+        };
+        let value = leaf(quote! { &self.value });
+        let child = leaf(quote! { &**reference });
+        // The `Weak` pointers are cloned after the other fields, which may hold their target.
+        let output = quote! {
+            impl<T> Node<T> where T: Clone + 'static {
+                fn clone_graph(&self) -> Self {
+                    self.clone_graph_in(&mut ::std::collections::HashMap::new())
+                }
+                #[doc(hidden)]
+                #[allow(unused_variables)]
+                fn clone_graph_in(&self, memo: &mut #memo_type) -> Self {
+                    Self {
+                        value: #value,
+                        children: (&self.children).iter().map(|value| {
+                            let reference = value;
+                            let key = <Rc<Node<T> > >::as_ptr(reference) as *const () as usize;
+                            let node = memo
+                                .get(&key)
+                                .and_then(|node| node.downcast_ref::<::std::rc::Weak<Node<T> > >())
+                                .cloned();
+                            match node {
+                                Some(node) => node.upgrade().expect("cannot clone a strong reference cycle"),
+                                None => <Rc<Node<T> > >::new_cyclic(|node| {
+                                    memo.insert(key, ::std::boxed::Box::new(node.clone()));
+                                    #child
+                                }),
+                            }
+                        }).collect::<Vec<Rc<Node<T> > > >(),
+                        parent: {
+                            let reference = &self.parent;
+                            let key = <Weak<Node<T> > >::as_ptr(reference) as *const () as usize;
+                            match memo.get(&key).and_then(|node| node.downcast_ref::<Weak<Node<T> > >()) {
+                                Some(node) => node.clone(),
+                                None => reference.clone(),
+                            }
+                        },
+                    }
+                }
+            }
+        };
+        let result = smart_graph_clone_derive(input).to_string();
+        assert_eq!(
+            result,
+            output.to_string(),
+            "Impl of #[derive(GraphClone)]: {}",
+            result
+        );
+    }
+
+    #[test]
+    fn test_profiles() {
+        let input = quote! {
+            pub struct Document<T> {
+                #[clone(profile = "template", default)]
+                id: u64,
+                title: String,
+                #[clone(default)]
+                #[clone(profile = "snapshot")]
+                #[clone(profile = "template", value = Vec::new())]
+                cache: Vec<T>,
+            }
+        };
+        let output = quote! {
+            impl<T> Clone for Document<T> {
+                fn clone(&self) -> Self {
+                    Self {
+                        id: ::core::clone::Clone::clone(&self.id),
+                        title: ::core::clone::Clone::clone(&self.title),
+                        cache: Default::default(),
+                    }
+                }
+                fn clone_from(&mut self, source: &Self) {
+                    self.id.clone_from(&source.id);
+                    self.title.clone_from(&source.title);
+                    self.cache = Default::default();
+                }
+            }
+            impl<T> Document<T> {
+                pub fn clone_template(&self) -> Self {
+                    Self {
+                        id: Default::default(),
+                        title: ::core::clone::Clone::clone(&self.title),
+                        cache: Vec::new(),
+                    }
+                }
+            }
+            impl<T> Document<T> where T: Clone {
+                pub fn clone_snapshot(&self) -> Self {
+                    Self {
+                        id: ::core::clone::Clone::clone(&self.id),
+                        title: ::core::clone::Clone::clone(&self.title),
+                        cache: ::core::clone::Clone::clone(&self.cache),
+                    }
+                }
+            }
+        };
+        let result = smart_clone_derive(input).to_string();
+        assert_eq!(
+            result,
+            output.to_string(),
+            "Impl with #[clone(profile = ...)] tag: {}",
+            result
+        );
+    }
+
+    #[test]
+    fn test_enum_profiles() {
+        let input = quote! {
+            enum Handle {
+                #[clone(profile = "persist", value = Handle::Closed)]
+                Open(u32, #[clone(profile = "persist", default)] String),
+                Closed,
+            }
+        };
+        let output = quote! {
+            impl Clone for Handle {
+                fn clone(&self) -> Self {
+                    match self {
+                        Handle::Open(v0, v1) => Handle::Open(::core::clone::Clone::clone(v0), ::core::clone::Clone::clone(v1)),
+                        Handle::Closed => Handle::Closed,
+                    }
+                }
+                fn clone_from(&mut self, source: &Self) {
+                    match (&mut *self, source) {
+                        (Handle::Open(self_v0, self_v1), Handle::Open(v0, v1)) => {
+                            self_v0.clone_from(v0);
+                            self_v1.clone_from(v1);
+                        },
+                        _ => *self = source.clone(),
+                    }
+                }
+            }
+            impl Handle {
+                fn clone_persist(&self) -> Self {
+                    match self {
+                        #[allow(unused_variables)]
+                        Handle::Open(v0, v1) => Handle::Closed,
+                        Handle::Closed => Handle::Closed,
+                    }
+                }
+            }
+        };
+        let result = smart_clone_derive(input).to_string();
+        assert_eq!(
+            result,
+            output.to_string(),
+            "Impl with variant profiles: {}",
+            result
+        );
+    }
+
+    #[test]
+    fn test_invalid_profiles() {
+        let input = quote! {
+            struct Invalid {
+                #[clone(default, profile = "template")]
+                a: u32,
+                #[clone(profile = "with space", default)]
+                b: u32,
+                #[clone(profile = "template", bound = "")]
+                c: u32,
+                #[clone(profile = "template", default)]
+                #[clone(profile = "template", value = 1)]
+                d: u32,
+                #[clone(profile = "from", default)]
+                e: u32,
+                #[clone(profile = "graph_in", default)]
+                f: u32,
+                #[clone(profile = "into_projection", default)]
+                g: u32,
+            }
+        };
+        let output = quote! {
+            ::core::compile_error! { "`profile` must be the first option of a `#[clone(...)]` list" }
+            ::core::compile_error! { "expected a profile name usable in `clone_<name>`" }
+            ::core::compile_error! { "`bound` cannot be set for a profile only" }
+            ::core::compile_error! { "conflicting clone options `default` and `value`" }
+            ::core::compile_error! { "`default` is first set here" }
+            ::core::compile_error! { "the profile name `from` is reserved: `clone_from` exists" }
+            ::core::compile_error! { "the profile name `graph_in` is reserved: `clone_graph_in` exists" }
+            ::core::compile_error! { "the profile name `into_projection` is reserved: `clone_into_projection` exists" }
+        };
+        let result = smart_clone_derive(input).to_string();
+        assert_eq!(result, output.to_string(), "Invalid profiles: {}", result);
+    }
+
+    #[test]
+    fn test_builder() {
+        let input = quote! {
+            #[clone(builder)]
+            pub struct Request<T> {
+                url: String,
+                #[clone(default)]
+                body: T,
+            }
+        };
+        let output = quote! {
+            impl<T> Clone for Request<T> {
+                fn clone(&self) -> Self {
+                    Self {
+                        url: ::core::clone::Clone::clone(&self.url),
+                        body: Default::default(),
+                    }
+                }
+                fn clone_from(&mut self, source: &Self) {
+                    self.url.clone_from(&source.url);
+                    self.body = Default::default();
+                }
+            }
+            #[doc = "A clone of a [`Request`] being built, where some fields are set instead of being cloned."]
+            pub struct RequestCloneBuilder<'source, T> {
+                source: &'source Request<T>,
+                url: ::core::option::Option<String>,
+                body: ::core::option::Option<T>,
+            }
+            impl<T> Request<T> {
+                /// Start a clone of this value, where some fields can be set instead of being cloned.
+                pub fn clone_builder<'source>(&'source self) -> RequestCloneBuilder<'source, T> {
+                    RequestCloneBuilder {
+                        source: self,
+                        url: ::core::option::Option::None,
+                        body: ::core::option::Option::None,
+                    }
+                }
+                #[doc(hidden)]
+                fn clone_builder_url(&self) -> String {
+                    ::core::clone::Clone::clone(&self.url)
+                }
+                #[doc(hidden)]
+                fn clone_builder_body(&self) -> T {
+                    Default::default()
+                }
+            }
+            impl<'source, T> RequestCloneBuilder<'source, T> {
+                #[doc = "Set `url` instead of cloning it."]
+                pub fn url(mut self, value: String) -> Self {
+                    self.url = ::core::option::Option::Some(value);
+                    self
+                }
+                #[doc = "Set `body` instead of cloning it."]
+                pub fn body(mut self, value: T) -> Self {
+                    self.body = ::core::option::Option::Some(value);
+                    self
+                }
+                /// Build the clone: the fields which are not set are cloned from the source.
+                pub fn build(self) -> Request<T> {
+                    Request {
+                        url: match self.url {
+                            ::core::option::Option::Some(value) => value,
+                            ::core::option::Option::None => self.source.clone_builder_url(),
+                        },
+                        body: match self.body {
+                            ::core::option::Option::Some(value) => value,
+                            ::core::option::Option::None => self.source.clone_builder_body(),
+                        },
+                    }
+                }
+            }
+        };
+        let result = smart_clone_derive(input).to_string();
+        assert_eq!(
+            result,
+            output.to_string(),
+            "Impl with #[clone(builder)]: {}",
+            result
+        );
+    }
+
+    #[test]
+    fn test_invalid_builder() {
+        let input = quote! {
+            #[clone(builder)]
+            enum Invalid {
+                A,
+            }
+        };
+        let output = quote! {
+            ::core::compile_error! { "`builder` is only supported on structs with named fields" }
+        };
+        let result = smart_clone_derive(input).to_string();
+        assert_eq!(result, output.to_string(), "Invalid builder: {}", result);
+
+        let input = quote! {
+            #[clone(builder)]
+            struct Invalid {
+                a: u32,
+                #[clone(value = a + 1, uses(a))]
+                b: u32,
+            }
+        };
+        let output = quote! {
+            ::core::compile_error! { "`uses` is not supported along with `builder`" }
+        };
+        let result = smart_clone_derive(input).to_string();
+        assert_eq!(result, output.to_string(), "Invalid builder: {}", result);
+
+        let input = quote! {
+            #[clone(builder)]
+            struct Invalid {
+                build: u32,
+            }
+        };
+        let output = quote! {
+            ::core::compile_error! { "a field named `build` is not supported along with `builder`: its setter would be the `build` method" }
+        };
+        let result = smart_clone_derive(input).to_string();
+        assert_eq!(result, output.to_string(), "Invalid builder: {}", result);
+
+        let input = quote! {
+            #[clone(builder)]
+            struct Invalid {
+                #[clone(profile = "builder_url", default)]
+                url: String,
+            }
+        };
+        let output = quote! {
+            ::core::compile_error! { "the profile name `builder_url` is reserved along with `builder`: `clone_builder_url` exists" }
+        };
+        let result = smart_clone_derive(input).to_string();
+        assert_eq!(result, output.to_string(), "Invalid builder: {}", result);
+    }
+
+    #[test]
+    fn test_builder_names() {
+        let input = quote! {
+            #[clone(builder)]
+            struct Span<'source> {
+                source: &'source str,
+                start: usize,
+            }
+        };
+        // The lifetime and the field borrowing the source are named apart from the struct's.
+        let output = quote! {
+            impl<'source> Clone for Span<'source> {
+                fn clone(&self) -> Self {
+                    Self {
+                        source: ::core::clone::Clone::clone(&self.source),
+                        start: ::core::clone::Clone::clone(&self.start),
+                    }
+                }
+                fn clone_from(&mut self, source: &Self) {
+                    self.source.clone_from(&source.source);
+                    self.start.clone_from(&source.start);
+                }
+            }
+            #[doc = "A clone of a [`Span`] being built, where some fields are set instead of being cloned."]
+            struct SpanCloneBuilder<'source_, 'source> {
+                source_: &'source_ Span<'source>,
+                source: ::core::option::Option<&'source str>,
+                start: ::core::option::Option<usize>,
+            }
+            impl<'source> Span<'source> {
+                /// Start a clone of this value, where some fields can be set instead of being cloned.
+                fn clone_builder<'source_>(&'source_ self) -> SpanCloneBuilder<'source_, 'source> {
+                    SpanCloneBuilder {
+                        source_: self,
+                        source: ::core::option::Option::None,
+                        start: ::core::option::Option::None,
+                    }
+                }
+                #[doc(hidden)]
+                fn clone_builder_source(&self) -> &'source str {
+                    ::core::clone::Clone::clone(&self.source)
+                }
+                #[doc(hidden)]
+                fn clone_builder_start(&self) -> usize {
+                    ::core::clone::Clone::clone(&self.start)
+                }
+            }
+            impl<'source_, 'source> SpanCloneBuilder<'source_, 'source> {
+                #[doc = "Set `source` instead of cloning it."]
+                fn source(mut self, value: &'source str) -> Self {
+                    self.source = ::core::option::Option::Some(value);
+                    self
+                }
+                #[doc = "Set `start` instead of cloning it."]
+                fn start(mut self, value: usize) -> Self {
+                    self.start = ::core::option::Option::Some(value);
+                    self
+                }
+                /// Build the clone: the fields which are not set are cloned from the source.
+                fn build(self) -> Span<'source> {
+                    Span {
+                        source: match self.source {
+                            ::core::option::Option::Some(value) => value,
+                            ::core::option::Option::None => self.source_.clone_builder_source(),
+                        },
+                        start: match self.start {
+                            ::core::option::Option::Some(value) => value,
+                            ::core::option::Option::None => self.source_.clone_builder_start(),
+                        },
+                    }
+                }
+            }
+        };
+        let result = smart_clone_derive(input).to_string();
+        assert_eq!(result, output.to_string(), "Builder names: {}", result);
+    }
+
+    #[test]
+    fn test_mask() {
+        let input = quote! {
+            #[clone(mask)]
+            struct User {
+                #[clone(keep)]
+                id: u64,
+                display_name: String,
+                #[clone(value = 3)]
+                retries: u32,
+            }
+        };
+        let output = quote! {
+            impl Clone for User {
+                fn clone(&self) -> Self {
+                    Self {
+                        id: ::core::clone::Clone::clone(&self.id),
+                        display_name: ::core::clone::Clone::clone(&self.display_name),
+                        retries: 3,
+                    }
+                }
+                fn clone_from(&mut self, source: &Self) {
+                    self.id.clone_from(&source.id);
+                    self.display_name.clone_from(&source.display_name);
+                    self.retries = 3;
+                }
+            }
+            #[doc = "A field of a [`User`], which can be reset by `clone_except` and `clone_only`."]
+            #[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+            enum UserField {
+                DisplayName,
+                Retries,
+            }
+            impl UserField {
+                /// The bit of this field in a set.
+                const fn bit(self) -> u64 {
+                    match self {
+                        Self::DisplayName => 1u64,
+                        Self::Retries => 2u64,
+                    }
+                }
+            }
+            #[doc = "A set of [`UserField`], as a bit set."]
+            #[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Default)]
+            struct UserFieldSet {
+                bits: u64,
+            }
+            impl UserFieldSet {
+                /// The set of no field.
+                const fn empty() -> Self {
+                    Self { bits: 0 }
+                }
+                /// The set of all the fields.
+                const fn all() -> Self {
+                    Self { bits: 3u64 }
+                }
+                /// Checks if the set contains the field.
+                const fn contains(self, field: UserField) -> bool {
+                    self.bits & field.bit() != 0
+                }
+                /// The set with the field added.
+                const fn with(self, field: UserField) -> Self {
+                    Self { bits: self.bits | field.bit() }
+                }
+                /// The set with the field removed.
+                const fn without(self, field: UserField) -> Self {
+                    Self { bits: self.bits & !field.bit() }
+                }
+                /// The set of the fields which are not in this one.
+                const fn complement(self) -> Self {
+                    Self { bits: !self.bits & 3u64 }
+                }
+            }
+            impl ::core::convert::From<UserField> for UserFieldSet {
+                fn from(field: UserField) -> Self {
+                    Self::empty().with(field)
+                }
+            }
+            impl ::core::ops::BitOr for UserField {
+                type Output = UserFieldSet;
+                fn bitor(self, other: Self) -> UserFieldSet {
+                    UserFieldSet::from(self).with(other)
+                }
+            }
+            impl ::core::ops::BitOr<UserField> for UserFieldSet {
+                type Output = Self;
+                fn bitor(self, field: UserField) -> Self {
+                    self.with(field)
+                }
+            }
+            impl ::core::ops::BitOr for UserFieldSet {
+                type Output = Self;
+                fn bitor(self, other: Self) -> Self {
+                    Self { bits: self.bits | other.bits }
+                }
+            }
+            impl ::core::iter::FromIterator<UserField> for UserFieldSet {
+                fn from_iter<I: ::core::iter::IntoIterator<Item = UserField>>(fields: I) -> Self {
+                    fields.into_iter().fold(Self::empty(), Self::with)
+                }
+            }
+            impl User {
+                /// Clone this value, resetting the fields of `mask` to their override, or to their
+                /// default value when they have none.
+                #[allow(unused_variables)]
+                fn clone_except(&self, mask: UserFieldSet) -> Self {
+                    Self {
+                        id: ::core::clone::Clone::clone(&self.id),
+                        display_name: match mask.contains(UserField::DisplayName) {
+                            true => <String as ::core::default::Default>::default(),
+                            false => ::core::clone::Clone::clone(&self.display_name),
+                        },
+                        retries: match mask.contains(UserField::Retries) {
+                            true => 3,
+                            false => 3,
+                        },
+                    }
+                }
+                /// Clone only the fields of `fields`, resetting the other ones as `clone_except` does.
+                fn clone_only(&self, fields: UserFieldSet) -> Self {
+                    self.clone_except(fields.complement())
+                }
+            }
+        };
+        let result = smart_clone_derive(input).to_string();
+        assert_eq!(
+            result,
+            output.to_string(),
+            "Impl with #[clone(mask)]: {}",
+            result
+        );
+    }
+
+    #[test]
+    fn test_invalid_mask() {
+        let input = quote! {
+            #[clone(mask)]
+            struct Invalid(u32);
+        };
+        let output = quote! {
+            ::core::compile_error! { "`mask` is only supported on structs with named fields" }
+        };
+        let result = smart_clone_derive(input).to_string();
+        assert_eq!(result, output.to_string(), "Invalid mask: {}", result);
+
+        let input = quote! {
+            struct Invalid {
+                #[clone(keep)]
+                a: u32,
+            }
+        };
+        let output = quote! {
+            ::core::compile_error! { "`keep` requires a `#[clone(mask)]` on the struct" }
+        };
+        let result = smart_clone_derive(input).to_string();
+        assert_eq!(result, output.to_string(), "Invalid mask: {}", result);
+
+        let input = quote! {
+            #[clone(mask)]
+            struct Invalid {
+                a: u32,
+                #[clone(value = a + 1, uses(a))]
+                b: u32,
+            }
+        };
+        let output = quote! {
+            ::core::compile_error! { "`uses` is not supported along with `mask`" }
+        };
+        let result = smart_clone_derive(input).to_string();
+        assert_eq!(result, output.to_string(), "Invalid mask: {}", result);
+    }
+
+    #[test]
+    fn test_into() {
+        let input = quote! {
+            #[clone(into = Snapshot, derive(Debug))]
+            pub struct Session<T, C: Connection> where C: Send {
+                pub user: T,
+                #[clone(exclude, with = C::reconnect)]
+                connection: C,
+                #[clone(default)]
+                requests: u32,
+            }
+        };
+        let output = quote! {
+            impl<T, C: Connection> Clone for Session<T, C>
+            where
+                C: Send,
+                T: Clone
+            {
+                fn clone(&self) -> Self {
+                    Self {
+                        user: ::core::clone::Clone::clone(&self.user),
+                        connection: C::reconnect(&self.connection),
+                        requests: Default::default(),
+                    }
+                }
+                fn clone_from(&mut self, source: &Self) {
+                    self.user.clone_from(&source.user);
+                    self.connection = C::reconnect(&source.connection);
+                    self.requests = Default::default();
+                }
+            }
+            #[doc = "A projection of [`Session`], without its excluded fields."]
+            #[derive(Debug)]
+            pub struct Snapshot<T> {
+                pub user: T,
+                requests: u32,
+            }
+            impl<T, C: Connection> Session<T, C>
+            where
+                C: Send,
+                T: Clone
+            {
+                #[doc(hidden)]
+                fn clone_into_projection(&self) -> Snapshot<T> {
+                    Snapshot {
+                        user: ::core::clone::Clone::clone(&self.user),
+                        requests: Default::default(),
+                    }
+                }
+            }
+            impl<T, C: Connection> ::core::convert::From<&Session<T, C> > for Snapshot<T>
+            where
+                C: Send,
+                T: Clone
+            {
+                fn from(value: &Session<T, C>) -> Self {
+                    value.clone_into_projection()
+                }
+            }
+        };
+        let result = smart_clone_derive(input).to_string();
+        assert_eq!(
+            result,
+            output.to_string(),
+            "Impl with #[clone(into = ...)]: {}",
+            result
+        );
+    }
+
+    #[test]
+    fn test_enum_into() {
+        let input = quote! {
+            #[clone(into = Snapshot)]
+            enum Event {
+                Key(#[clone(exclude)] u64, char),
+                Click { x: i32, #[clone(exclude)] source: Source },
+                Quit,
+            }
+        };
+        let output = quote! {
+            impl Clone for Event {
+                fn clone(&self) -> Self {
+                    match self {
+                        Event::Key(v0, v1) => Event::Key(::core::clone::Clone::clone(v0), ::core::clone::Clone::clone(v1)),
+                        Event::Click { x, source } => Event::Click { x: ::core::clone::Clone::clone(x), source: ::core::clone::Clone::clone(source) },
+                        Event::Quit => Event::Quit,
+                    }
+                }
+                fn clone_from(&mut self, source: &Self) {
+                    match (&mut *self, source) {
+                        (Event::Key(self_v0, self_v1), Event::Key(v0, v1)) => {
+                            self_v0.clone_from(v0);
+                            self_v1.clone_from(v1);
+                        },
+                        (Event::Click { x: self_x, source: self_source }, Event::Click { x, source }) => {
+                            self_x.clone_from(x);
+                            self_source.clone_from(source);
+                        },
+                        _ => *self = source.clone(),
+                    }
+                }
+            }
+            #[doc = "A projection of [`Event`], without its excluded fields."]
+            enum Snapshot {
+                Key(char),
+                Click { x: i32, },
+                Quit,
+            }
+            impl Event {
+                #[doc(hidden)]
+                fn clone_into_projection(&self) -> Snapshot {
+                    match self {
+                        Event::Key(_, v1) => Snapshot::Key(::core::clone::Clone::clone(v1)),
+                        Event::Click { x, .. } => Snapshot::Click { x: ::core::clone::Clone::clone(x) },
+                        Event::Quit => Snapshot::Quit,
+                    }
+                }
+            }
+            impl ::core::convert::From<&Event> for Snapshot {
+                fn from(value: &Event) -> Self {
+                    value.clone_into_projection()
+                }
+            }
+        };
+        let result = smart_clone_derive(input).to_string();
+        assert_eq!(
+            result,
+            output.to_string(),
+            "Impl with #[clone(into = ...)]: {}",
+            result
+        );
+    }
+
+    #[test]
+    fn test_invalid_into() {
+        let input = quote! {
+            #[clone(derive(Debug))]
+            struct Invalid {
+                #[clone(exclude)]
+                a: u32,
+            }
+        };
+        let output = quote! {
+            ::core::compile_error! { "`derive` requires an `into`: it applies to the generated type" }
+            ::core::compile_error! { "`exclude` requires a `#[clone(into = ...)]` on the struct or enum" }
+        };
+        let result = smart_clone_derive(input).to_string();
+        assert_eq!(result, output.to_string(), "Invalid into: {}", result);
+
+        let input = quote! {
+            #[clone(into = Snapshot)]
+            enum Invalid {
+                #[clone(default)]
+                A(u32),
+            }
+        };
+        let output = quote! {
+            ::core::compile_error! { "the override of a variant is not a `Snapshot`: only the fields of a variant can be overridden along with `into`" }
+        };
+        let result = smart_clone_derive(input).to_string();
+        assert_eq!(result, output.to_string(), "Invalid into: {}", result);
+    }
+
+    #[test]
+    fn test_remap() {
+        let input = quote! {
+            enum Task<J, H> {
+                Pending { job: J, retries: u32 },
+                #[clone(to = Pending)]
+                Running { job: J, handle: H },
+                #[clone(to = Done)]
+                Stopped(u64, H),
+                Done(u64),
+                #[clone(to = Done)]
+                Idle,
+            }
+        };
+        let output = quote! {
+            impl<J, H> Clone for Task<J, H>
+            where
+                J: Clone
+            {
+                fn clone(&self) -> Self {
+                    match self {
+                        Task::Pending { job, retries } => Task::Pending { job: ::core::clone::Clone::clone(job), retries: ::core::clone::Clone::clone(retries) },
+                        Task::Running { job, .. } => Task::Pending {
+                            job: ::core::clone::Clone::clone(job),
+                            retries: <u32 as ::core::default::Default>::default()
+                        },
+                        Task::Stopped(v0, _) => Task::Done(::core::clone::Clone::clone(v0)),
+                        Task::Done(v0) => Task::Done(::core::clone::Clone::clone(v0)),
+                        Task::Idle => Task::Done(<u64 as ::core::default::Default>::default()),
+                    }
+                }
+                fn clone_from(&mut self, source: &Self) {
+                    match (&mut *self, source) {
+                        (Task::Pending { job: self_job, retries: self_retries }, Task::Pending { job, retries }) => {
+                            self_job.clone_from(job);
+                            self_retries.clone_from(retries);
+                        },
+                        (Task::Done(self_v0), Task::Done(v0)) => {
+                            self_v0.clone_from(v0);
+                        },
+                        _ => *self = source.clone(),
+                    }
+                }
+            }
+        };
+        let result = smart_clone_derive(input).to_string();
+        assert_eq!(
+            result,
+            output.to_string(),
+            "Impl with #[clone(to = ...)]: {}",
+            result
+        );
+    }
+
+    #[test]
+    fn test_invalid_remap() {
+        let input = quote! {
+            enum Invalid {
+                A { x: u32 },
+                #[clone(to = C)]
+                B { x: u32 },
+                #[clone(to = D)]
+                D(u32),
+                #[clone(to = A)]
+                E(u32),
+                #[clone(to = A)]
+                F { x: u64 },
+            }
+        };
+        let output = quote! {
+            ::core::compile_error! { "unknown variant in `to`" }
+            ::core::compile_error! { "`to` must name another variant" }
+            ::core::compile_error! { "`E` and `A` have different kinds of fields: `to` moves named fields to named fields, unnamed fields to unnamed fields" }
+            ::core::compile_error! { "mismatched types in `to = A`: field `x` is a `u32` in `A`" }
+        };
+        let result = smart_clone_derive(input).to_string();
+        assert_eq!(result, output.to_string(), "Invalid remap: {}", result);
+    }
+
+    #[test]
+    fn test_remap_type_spellings() {
+        let input = quote! {
+            enum Request<T> {
+                Queued { name: String, items: Vec<T> },
+                #[clone(to = Queued)]
+                Sent { name: std::string::String, items: alloc::vec::Vec<T> },
+            }
+        };
+        // `std::string::String` is a `String` and `alloc::vec::Vec<T>` a `Vec<T>`: no mismatch.
+        let output = quote! {
+            impl<T> Clone for Request<T>
+            where
+                T: Clone
+            {
+                fn clone(&self) -> Self {
+                    match self {
+                        Request::Queued { name, items } => Request::Queued {
+                            name: ::core::clone::Clone::clone(name),
+                            items: ::core::clone::Clone::clone(items)
+                        },
+                        Request::Sent { name, items } => Request::Queued {
+                            name: ::core::clone::Clone::clone(name),
+                            items: ::core::clone::Clone::clone(items)
+                        },
+                    }
+                }
+                fn clone_from(&mut self, source: &Self) {
+                    match (&mut *self, source) {
+                        (Request::Queued { name: self_name, items: self_items }, Request::Queued { name, items }) => {
+                            self_name.clone_from(name);
+                            self_items.clone_from(items);
+                        },
+                        _ => *self = source.clone(),
+                    }
+                }
+            }
+        };
+        let result = smart_clone_derive(input).to_string();
+        assert_eq!(
+            result,
+            output.to_string(),
+            "Remap type spellings: {}",
+            result
+        );
+    }
+
+    #[test]
+    fn test_reset() {
+        let input = quote! {
+            enum Connection<T> {
+                #[clone(reset)]
+                Open(T, #[clone(value = 3)] u8),
+                #[clone(reset)]
+                Closed { reason: String, #[clone(with = Clone::clone)] since: u64 },
+                #[clone(reset)]
+                Idle,
+            }
+        };
+        let output = quote! {
+            impl<T> Clone for Connection<T> {
+                fn clone(&self) -> Self {
+                    match self {
+                        Connection::Open(_, _) => Connection::Open(<T as ::core::default::Default>::default(), 3),
+                        Connection::Closed { since, .. } => Connection::Closed {
+                            reason: <String as ::core::default::Default>::default(),
+                            since: Clone::clone(since)
+                        },
+                        Connection::Idle => Connection::Idle,
+                    }
+                }
+            }
+        };
+        let result = smart_clone_derive(input).to_string();
+        assert_eq!(
+            result,
+            output.to_string(),
+            "Impl with #[clone(reset)]: {}",
+            result
+        );
+    }
+
+    #[test]
+    fn test_with_fields() {
+        let input = quote! {
+            enum Shape {
+                #[clone(with_fields = Shape::scaled)]
+                Circle { x: i32, y: i32, radius: u32 },
+                #[clone(with_fields = |w: &u32, h: &u32| Shape::Rect(*h, *w))]
+                Rect(u32, u32),
+                #[clone(value = Shape::Square(v0 + 1))]
+                Square(u32),
+                #[clone(with_fields = Shape::point)]
+                Point,
+            }
+        };
+        let output = quote! {
+            impl Clone for Shape {
+                fn clone(&self) -> Self {
+                    match self {
+                        Shape::Circle { x, y, radius } => Shape::scaled(x, y, radius),
+                        Shape::Rect(v0, v1) => (|w: &u32, h: &u32| Shape::Rect(*h, *w))(v0, v1),
+                        #[allow(unused_variables)]
+                        Shape::Square(v0) => Shape::Square(v0 + 1),
+                        Shape::Point => Shape::point(),
+                    }
+                }
+            }
+        };
+        let result = smart_clone_derive(input).to_string();
+        assert_eq!(
+            result,
+            output.to_string(),
+            "Impl with #[clone(with_fields = ...)]: {}",
+            result
+        );
+    }
+
+    #[test]
+    fn test_never() {
+        let input = quote! {
+            enum Transaction {
+                #[clone(panic = "commit or roll back first")]
+                InFlight(Handle),
+                Done { id: u64, #[clone(unreachable)] lock: Lock },
+                Idle,
+            }
+        };
+        let output = quote! {
+            impl Clone for Transaction {
+                fn clone(&self) -> Self {
+                    match self {
+                        Transaction::InFlight(..) => ::core::panic!(
+                            "cannot clone variant `{}::InFlight`: {}",
+                            ::core::any::type_name::<Self>(),
+                            "commit or roll back first"
+                        ),
+                        Transaction::Done { .. } => ::core::unreachable!(
+                            "cannot clone field `lock` of `{}::Done`",
+                            ::core::any::type_name::<Self>()
+                        ),
+                        Transaction::Idle => Transaction::Idle,
+                    }
+                }
+            }
+        };
+        let result = smart_clone_derive(input).to_string();
+        assert_eq!(
+            result,
+            output.to_string(),
+            "Impl with #[clone(panic = ...)] and #[clone(unreachable)]: {}",
+            result
+        );
+    }
+
+    #[test]
+    fn test_forbid() {
+        let input = quote! {
+            struct Session {
+                id: u64,
+                #[clone(forbid)]
+                socket: Socket,
+            }
+        };
+        let output = quote! {
+            impl Session {
+                #[allow(unreachable_code)]
+                fn try_clone(&self) -> ::core::result::Result<Self, ::std::boxed::Box<dyn ::std::error::Error + ::core::marker::Send + ::core::marker::Sync> > {
+                    ::core::result::Result::Ok(return ::core::result::Result::Err(::core::convert::From::from(
+                        ::std::format!("cannot clone field `socket` of `{}`", ::core::any::type_name::<Self>())
+                    )))
+                }
+            }
+        };
+        let result = smart_try_clone_derive(input).to_string();
+        assert_eq!(
+            result,
+            output.to_string(),
+            "Impl with #[clone(forbid)]: {}",
+            result
+        );
+    }
+
+    #[test]
+    fn test_invalid_never() {
+        let input = quote! {
+            #[clone(mask)]
+            struct Session {
+                id: u64,
+                #[clone(forbid)]
+                socket: Socket,
+            }
+        };
+        let output = quote! {
+            ::core::compile_error! { "`panic`, `unreachable` and `forbid` are not supported along with `mask`" }
+        };
+        let result = smart_clone_derive(input).to_string();
+        assert_eq!(
+            result,
+            output.to_string(),
+            "Impl with #[clone(forbid)] along with mask: {}",
+            result
+        );
+    }
+
+    #[test]
+    fn test_default_fields() {
+        let input = quote! {
+            #[clone(default_fields = "cache_*, *_scratch")]
+            struct Renderer {
+                name: String,
+                cache_glyphs: Vec<u8>,
+                cache_layout: Vec<u8>,
+                #[clone]
+                cache_shared: Arc<Vec<u8>>,
+                #[clone(value = Vec::with_capacity(64))]
+                line_scratch: Vec<u8>,
+            }
+        };
+        let output = quote! {
+            impl Clone for Renderer {
+                fn clone(&self) -> Self {
+                    Self {
+                        name: ::core::clone::Clone::clone(&self.name),
+                        cache_glyphs: Default::default(),
+                        cache_layout: Default::default(),
+                        cache_shared: ::core::clone::Clone::clone(&self.cache_shared),
+                        line_scratch: Vec::with_capacity(64),
+                    }
+                }
+                fn clone_from(&mut self, source: &Self) {
+                    self.name.clone_from(&source.name);
+                    self.cache_glyphs = Default::default();
+                    self.cache_layout = Default::default();
+                    self.cache_shared.clone_from(&source.cache_shared);
+                    self.line_scratch = Vec::with_capacity(64);
+                }
+            }
+        };
+        let result = smart_clone_derive(input).to_string();
+        assert_eq!(
+            result,
+            output.to_string(),
+            "Impl with #[clone(default_fields = ...)]: {}",
+            result
+        );
+    }
+
+    #[test]
+    fn test_all_except() {
+        let input = quote! {
+            #[clone(all = default, except(id, name))]
+            enum Record {
+                User { id: u64, name: String, session: Session },
+                Guest(Session),
+            }
+        };
+        let output = quote! {
+            impl Clone for Record {
+                fn clone(&self) -> Self {
+                    match self {
+                        Record::User { id, name, .. } => Record::User {
+                            id: ::core::clone::Clone::clone(id),
+                            name: ::core::clone::Clone::clone(name),
+                            session: Default::default()
+                        },
+                        Record::Guest(_) => Record::Guest(Default::default()),
+                    }
+                }
+                fn clone_from(&mut self, source: &Self) {
+                    match (&mut *self, source) {
+                        (Record::User { id: self_id, name: self_name, session: self_session }, Record::User { id, name, .. }) => {
+                            self_id.clone_from(id);
+                            self_name.clone_from(name);
+                            *self_session = Default::default();
+                        },
+                        (Record::Guest(self_v0), Record::Guest(_)) => {
+                            *self_v0 = Default::default();
+                        },
+                        _ => *self = source.clone(),
+                    }
+                }
+            }
+        };
+        let result = smart_clone_derive(input).to_string();
+        assert_eq!(
+            result,
+            output.to_string(),
+            "Impl with #[clone(all = default, except(...))]: {}",
+            result
+        );
+    }
+
+    #[test]
+    fn test_invalid_policies() {
+        let input = quote! {
+            #[clone(default_fields = "cache_*, *_scratch", except(name))]
+            struct Renderer {
+                name: String,
+                cache_glyphs: Vec<u8>,
+            }
+        };
+        let output = quote! {
+            ::core::compile_error! { "`except` requires an `all`: it lists the fields left out of it" }
+            ::core::compile_error! { "`*_scratch` matches no field" }
+        };
+        let result = smart_clone_derive(input).to_string();
+        assert_eq!(
+            result,
+            output.to_string(),
+            "Impl with invalid policies: {}",
+            result
+        );
+    }
+
+    #[test]
+    fn test_invalid_selectors() {
+        let input = quote! {
+            #[clone(default_fields = "cache-*")]
+            #[clone(all = shallow)]
+            struct Renderer {
+                name: String,
+            }
+        };
+        let output = quote! {
+            ::core::compile_error! { "invalid field selector `cache-*`: expected a field name, where `*` matches any characters" }
+            ::core::compile_error! { "expected `all = default` or `all = deep`" }
+        };
+        let result = smart_clone_derive(input).to_string();
+        assert_eq!(
+            result,
+            output.to_string(),
+            "Impl with invalid selectors: {}",
+            result
+        );
+    }
+
+    #[test]
+    fn test_for_type() {
+        let input = quote! {
+            #[clone(for_type(std::time::Instant = "Instant::now()"), for_type(Option<JoinHandle<()>> = default))]
+            struct Worker {
+                name: String,
+                started: Instant,
+                #[clone]
+                deadline: time::Instant,
+                handle: Option<thread::JoinHandle<()>>,
+                workers: Vec<Option<JoinHandle<()>>>,
+            }
+        };
+        // Shorter paths also match in the generic arguments: `Option<thread::JoinHandle<()>>` is
+        // selected by the rule for `Option<JoinHandle<()>>`, but `Vec<...>` is not.
+        let output = quote! {
+            impl Clone for Worker {
+                fn clone(&self) -> Self {
+                    Self {
+                        name: ::core::clone::Clone::clone(&self.name),
+                        started: Instant::now(),
+                        deadline: ::core::clone::Clone::clone(&self.deadline),
+                        handle: Default::default(),
+                        workers: ::core::clone::Clone::clone(&self.workers),
+                    }
+                }
+                fn clone_from(&mut self, source: &Self) {
+                    self.name.clone_from(&source.name);
+                    self.started = Instant::now();
+                    self.deadline.clone_from(&source.deadline);
+                    self.handle = Default::default();
+                    self.workers.clone_from(&source.workers);
+                }
+            }
+        };
+        let result = smart_clone_derive(input).to_string();
+        assert_eq!(
+            result,
+            output.to_string(),
+            "Impl with #[clone(for_type(...))]: {}",
+            result
+        );
+    }
+
+    #[test]
+    fn test_enum_for_type() {
+        let input = quote! {
+            #[clone(for_type(Sender<Event> = "channel().0"), default_fields = "cache")]
+            enum Stage {
+                Running { events: Sender<Event>, cache: Sender<Event> },
+                Stopped(Sender<Event>, u8),
+            }
+        };
+        let output = quote! {
+            impl Clone for Stage {
+                fn clone(&self) -> Self {
+                    match self {
+                        Stage::Running { .. } => Stage::Running {
+                            events: channel().0,
+                            cache: channel().0
+                        },
+                        Stage::Stopped(_, v1) => Stage::Stopped(channel().0, ::core::clone::Clone::clone(v1)),
+                    }
+                }
+                fn clone_from(&mut self, source: &Self) {
+                    match (&mut *self, source) {
+                        (Stage::Running { events: self_events, cache: self_cache }, Stage::Running { .. }) => {
+                            *self_events = channel().0;
+                            *self_cache = channel().0;
+                        },
+                        (Stage::Stopped(self_v0, self_v1), Stage::Stopped(_, v1)) => {
+                            *self_v0 = channel().0;
+                            self_v1.clone_from(v1);
+                        },
+                        _ => *self = source.clone(),
+                    }
+                }
+            }
+        };
+        let result = smart_clone_derive(input).to_string();
+        assert_eq!(
+            result,
+            output.to_string(),
+            "Impl with #[clone(for_type(...))] on an enum: {}",
+            result
+        );
+    }
+
+    #[test]
+    fn test_invalid_for_type() {
+        let input = quote! {
+            #[clone(for_type(Instant = "Instant::now()"), for_type(Instant = default))]
+            #[clone(for_type(Duration = shallow))]
+            #[clone(for_type(JoinHandle<()> = default))]
+            struct Worker {
+                started: Instant,
+            }
+        };
+        let output = quote! {
+            ::core::compile_error! { "duplicate `for_type` rule for `Instant`" }
+            ::core::compile_error! { "expected `default`, `deep` or an expression as a string" }
+            ::core::compile_error! { "no field has the type `JoinHandle<()>`" }
+        };
+        let result = smart_clone_derive(input).to_string();
+        assert_eq!(
+            result,
+            output.to_string(),
+            "Impl with invalid #[clone(for_type(...))]: {}",
+            result
+        );
+    }
+
+    #[test]
+    fn test_set() {
+        let input = quote! {
+            struct Client {
+                #[clone(set(session.token = None, session.retries = 0))]
+                settings: Settings,
+                #[clone(set(0 = self.generation + 1))]
+                version: (u64, String),
+            }
+        };
+        let output = quote! {
+            impl Clone for Client {
+                fn clone(&self) -> Self {
+                    Self {
+                        settings: {
+                            let mut value = ::core::clone::Clone::clone(&self.settings);
+                            value.session.token = None;
+                            value.session.retries = 0;
+                            value
+                        },
+                        version: {
+                            let mut value = ::core::clone::Clone::clone(&self.version);
+                            value.0 = self.generation + 1;
+                            value
+                        },
+                    }
+                }
+            }
+        };
+        let result = smart_clone_derive(input).to_string();
+        assert_eq!(
+            result,
+            output.to_string(),
+            "Impl with #[clone(set(...))]: {}",
+            result
+        );
+    }
+
+    #[test]
+    fn test_invalid_set() {
+        let input = quote! {
+            enum Client {
+                Connected(#[clone(set())] Settings),
+                #[clone(set(session = None))]
+                Offline,
+            }
+        };
+        let output = quote! {
+            ::core::compile_error! { "expected at least one `field.path = value` in `set(...)`" }
+            ::core::compile_error! { "unknown clone option `set`, expected one of: `value`, `default`, `with`, `clone_with`, `with_fields`, `with_ctx`, `try_with`, `to`, `reset`, `panic`, `unreachable`, `forbid`, `map`, `bound`, `profile`" }
+        };
+        let result = smart_clone_derive(input).to_string();
+        assert_eq!(
+            result,
+            output.to_string(),
+            "Impl with invalid #[clone(set(...))]: {}",
+            result
+        );
+    }
+
+    #[test]
+    fn test_map() {
+        let input = quote! {
+            struct Document {
+                #[clone(map = |name: String| name + " (copy)")]
+                name: String,
+                #[clone(deep, map = Self::detach)]
+                pages: Vec<Rc<Page>>,
+                #[clone(default, map = Version::bump)]
+                version: Version,
+            }
+        };
+        let output = quote! {
+            impl Clone for Document {
+                fn clone(&self) -> Self {
+                    Self {
+                        name: (|name: String| name + " (copy)")(::core::clone::Clone::clone(&self.name)),
+                        pages: Self::detach(
+                            (&self.pages)
+                                .iter()
+                                .map(|value| <Rc<Page> >::new({
+                                    #[allow(dead_code)]
+                                    trait DeepCloneFallback: Clone {
+                                        fn deep_clone(&self) -> Self {
+                                            self.clone()
+                                        }
+                                    }
+                                    impl<T: Clone> DeepCloneFallback for T {}
+                                    (&**value).deep_clone()
+                                }))
+                                .collect::<Vec<Rc<Page> > >()
+                        ),
+                        version: Version::bump(Default::default()),
+                    }
+                }
+                fn clone_from(&mut self, source: &Self) {
+                    self.name = (|name: String| name + " (copy)")(::core::clone::Clone::clone(&source.name));
+                    self.pages = Self::detach(
+                        (&source.pages)
+                            .iter()
+                            .map(|value| <Rc<Page> >::new({
+                                #[allow(dead_code)]
+                                trait DeepCloneFallback: Clone {
+                                    fn deep_clone(&self) -> Self {
+                                        self.clone()
+                                    }
+                                }
+                                impl<T: Clone> DeepCloneFallback for T {}
+                                (&**value).deep_clone()
+                            }))
+                            .collect::<Vec<Rc<Page> > >()
+                    );
+                    self.version = Version::bump(Default::default());
+                }
+            }
+        };
+        let result = smart_clone_derive(input).to_string();
+        assert_eq!(
+            result,
+            output.to_string(),
+            "Impl with #[clone(map = ...)]: {}",
+            result
+        );
+    }
+
+    #[test]
+    fn test_enum_map() {
+        let input = quote! {
+            enum Node {
+                #[clone(map = Node::renamed)]
+                Leaf { name: String, #[clone(map = bump)] version: u32 },
+                Branch(Vec<Node>, #[clone(map = bump)] u32),
+                #[clone(panic = "locked", map = Node::renamed)]
+                Locked,
+            }
+        };
+        let output = quote! {
+            impl Clone for Node {
+                fn clone(&self) -> Self {
+                    match self {
+                        Node::Leaf { name, version } => Node::renamed(Node::Leaf {
+                            name: ::core::clone::Clone::clone(name),
+                            version: bump(::core::clone::Clone::clone(version))
+                        }),
+                        Node::Branch(v0, v1) => Node::Branch(::core::clone::Clone::clone(v0), bump(::core::clone::Clone::clone(v1))),
+                        Node::Locked => ::core::panic!(
+                            "cannot clone variant `{}::Locked`: {}",
+                            ::core::any::type_name::<Self>(),
+                            "locked"
+                        ),
+                    }
+                }
+                fn clone_from(&mut self, source: &Self) {
+                    match (&mut *self, source) {
+                        (Node::Branch(self_v0, self_v1), Node::Branch(v0, v1)) => {
+                            self_v0.clone_from(v0);
+                            *self_v1 = bump(::core::clone::Clone::clone(v1));
+                        },
+                        _ => *self = source.clone(),
+                    }
+                }
+            }
+        };
+        let result = smart_clone_derive(input).to_string();
+        assert_eq!(
+            result,
+            output.to_string(),
+            "Impl with #[clone(map = ...)] on an enum: {}",
+            result
+        );
+    }
+
+    #[test]
+    fn test_reference_field() {
+        let input = quote! {
+            struct View<'a, T, U> {
+                data: &'a T,
+                items: Vec<&'a U>,
+                raw: *const U,
+                callback: fn(T) -> U,
+            }
+        };
+        let output = quote! {
+            impl<'a, T, U> Clone for View<'a, T, U> {
+                fn clone(&self) -> Self {
+                    Self {
+                        data: ::core::clone::Clone::clone(&self.data),
+                        items: ::core::clone::Clone::clone(&self.items),
+                        raw: ::core::clone::Clone::clone(&self.raw),
+                        callback: ::core::clone::Clone::clone(&self.callback),
+                    }
+                }
+                fn clone_from(&mut self, source: &Self) {
+                    self.data.clone_from(&source.data);
+                    self.items.clone_from(&source.items);
+                    self.raw.clone_from(&source.raw);
+                    self.callback.clone_from(&source.callback);
+                }
+            }
+        };
+        let result = smart_clone_derive(input).to_string();
+        assert_eq!(result, output.to_string(), "Reference field: {}", result);
+    }
+
+    #[test]
+    fn test_enum_clone_from_bindings() {
+        let input = quote! {
+            enum Token {
+                Word {
+                    r#type: Kind,
+                    x: u32,
+                    self_x: u32,
+                    source: String,
+                    #[clone(with_parent = locate)]
+                    position: usize,
+                },
+            }
+        };
+        // The destination bindings and the `source` parameter are hygienic: they cannot collide
+        // with the fields of the same name.
+        let output = quote! {
+            impl Clone for Token {
+                fn clone(&self) -> Self {
+                    match self {
+                        Token::Word { r#type, x, self_x, source, position } => Token::Word {
+                            r#type: ::core::clone::Clone::clone(r#type),
+                            x: ::core::clone::Clone::clone(x),
+                            self_x: ::core::clone::Clone::clone(self_x),
+                            source: ::core::clone::Clone::clone(source),
+                            position: locate(position, self)
+                        },
+                    }
+                }
+                fn clone_from(&mut self, source: &Self) {
+                    match (&mut *self, source) {
+                        (
+                            Token::Word { r#type: self_type, x: self_x, self_x: self_self_x, source: self_source, position: self_position },
+                            Token::Word { r#type, x, self_x, source, position }
+                        ) => {
+                            self_type.clone_from(r#type);
+                            self_x.clone_from(x);
+                            self_self_x.clone_from(self_x);
+                            self_source.clone_from(source);
+                            *self_position = locate(position, source);
+                        },
+                        _ => *self = source.clone(),
+                    }
+                }
+            }
+        };
+        let result = smart_clone_derive(input).to_string();
+        assert_eq!(
+            result,
+            output.to_string(),
+            "Enum clone_from bindings: {}",
+            result
+        );
+    }
+
+    #[test]
+    fn test_context_field_named_ctx() {
+        let input = quote! {
+            #[clone(context = IdAllocator, skip_clone)]
+            enum Node {
+                Leaf {
+                    ctx: String,
+                    #[clone(with_ctx = IdAllocator::next)]
+                    id: u64,
+                },
+            }
+        };
+        // The `ctx` parameter is hygienic: the field of the same name does not shadow it.
+        let output = quote! {
+            impl Node {
+                #[allow(unused_variables)]
+                fn clone_with_ctx(&self, ctx: &mut IdAllocator) -> Self {
+                    match self {
+                        Node::Leaf { ctx, id } => Node::Leaf {
+                            ctx: ::core::clone::Clone::clone(ctx),
+                            id: IdAllocator::next(id, &mut *ctx)
+                        },
+                    }
+                }
+            }
+        };
+        let result = smart_clone_derive(input).to_string();
+        assert_eq!(result, output.to_string(), "Field named ctx: {}", result);
+    }
+
+    #[test]
+    fn test_deep_unsized() {
+        let input = quote! {
+            struct Document {
+                #[clone(deep)]
+                title: Arc<str>,
+                #[clone(deep)]
+                pages: Option<Box<[Page]>>,
+                #[clone(deep)]
+                renderer: Rc<dyn Render>,
+            }
+        };
+        // The pointers to an unsized value are cloned as usual.
+        let output = quote! {
+            impl Clone for Document {
+                fn clone(&self) -> Self {
+                    Self {
+                        title: {
+                            #[allow(dead_code)]
+                            trait DeepCloneFallback: Clone {
+                                fn deep_clone(&self) -> Self {
+                                    self.clone()
+                                }
+                            }
+                            impl<T: Clone> DeepCloneFallback for T {}
+                            (&self.title).deep_clone()
+                        },
+                        pages: (&self.pages).as_ref().map(|value| {
+                            #[allow(dead_code)]
+                            trait DeepCloneFallback: Clone {
+                                fn deep_clone(&self) -> Self {
+                                    self.clone()
+                                }
+                            }
+                            impl<T: Clone> DeepCloneFallback for T {}
+                            (value).deep_clone()
+                        }),
+                        renderer: {
+                            #[allow(dead_code)]
+                            trait DeepCloneFallback: Clone {
+                                fn deep_clone(&self) -> Self {
+                                    self.clone()
+                                }
+                            }
+                            impl<T: Clone> DeepCloneFallback for T {}
+                            (&self.renderer).deep_clone()
+                        },
+                    }
+                }
+                fn clone_from(&mut self, source: &Self) {
+                    self.title = {
+                        #[allow(dead_code)]
+                        trait DeepCloneFallback: Clone {
+                            fn deep_clone(&self) -> Self {
+                                self.clone()
+                            }
+                        }
+                        impl<T: Clone> DeepCloneFallback for T {}
+                        (&source.title).deep_clone()
+                    };
+                    self.pages = (&source.pages).as_ref().map(|value| {
+                        #[allow(dead_code)]
+                        trait DeepCloneFallback: Clone {
+                            fn deep_clone(&self) -> Self {
+                                self.clone()
+                            }
+                        }
+                        impl<T: Clone> DeepCloneFallback for T {}
+                        (value).deep_clone()
+                    });
+                    self.renderer = {
+                        #[allow(dead_code)]
+                        trait DeepCloneFallback: Clone {
+                            fn deep_clone(&self) -> Self {
+                                self.clone()
+                            }
+                        }
+                        impl<T: Clone> DeepCloneFallback for T {}
+                        (&source.renderer).deep_clone()
+                    };
+                }
+            }
+        };
+        let result = smart_clone_derive(input).to_string();
+        assert_eq!(result, output.to_string(), "Deep unsized: {}", result);
+    }
+
+    #[test]
+    fn test_graph_weak_first() {
+        let input = quote! {
+            enum Doc {
+                Tree { cursor: Weak<u32>, root: Rc<u32> },
+                Pair(Option<Weak<u32>>, Rc<u32>),
+            }
+        };
+        let memo_type = quote! {
+            ::std::collections::HashMap<usize, ::std::boxed::Box<dyn ::std::any::Any>>
+        };
+        let node = |reference| {
+            quote! {
+                {
+                    let reference = #reference;
+                    let key = <Rc<u32> >::as_ptr(reference) as *const () as usize;
+                    let node = memo
+                        .get(&key)
+                        .and_then(|node| node.downcast_ref::<::std::rc::Weak<u32> >())
+                        .cloned();
+                    match node {
+                        Some(node) => node.upgrade().expect("cannot clone a strong reference cycle"),
+                        None => <Rc<u32> >::new_cyclic(|node| {
+                            memo.insert(key, ::std::boxed::Box::new(node.clone()));
+                            {
+                                #[allow(dead_code)]
+                                trait GraphCloneFallback: Clone {
+                                    fn clone_graph_in(&self, _memo: &mut #memo_type) -> Self {
+                                        self.clone()
+                                    }
+                                }
+                                impl<T: Clone> GraphCloneFallback for T {}
+                                (&**reference).clone_graph_in(&mut *memo)
+                            }
+                        }),
+                    }
+                }
+            }
+        };
+        let weak = |reference| {
+            quote! {
+                {
+                    let reference = #reference;
+                    let key = <Weak<u32> >::as_ptr(reference) as *const () as usize;
+                    match memo.get(&key).and_then(|node| node.downcast_ref::<Weak<u32> >()) {
+                        Some(node) => node.clone(),
+                        None => reference.clone(),
+                    }
+                }
+            }
+        };
+        let (root, cursor) = (node(quote! { root }), weak(quote! { cursor }));
+        let (v1, v0) = (node(quote! { v1 }), weak(quote! { value }));
+        // The `Weak` pointers are cloned last, their target being cloned first whatever the order
+        // of the fields.
+        let output = quote! {
+            impl Doc {
+                fn clone_graph(&self) -> Self {
+                    self.clone_graph_in(&mut ::std::collections::HashMap::new())
+                }
+                #[doc(hidden)]
+                #[allow(unused_variables)]
+                fn clone_graph_in(&self, memo: &mut #memo_type) -> Self {
+                    match self {
+                        Doc::Tree { cursor, root } => Doc::Tree { root: #root, cursor: #cursor },
+                        Doc::Pair(v0, v1) => Doc::Pair {
+                            1: #v1,
+                            0: (v0).as_ref().map(|value| #v0)
+                        },
+                    }
+                }
+            }
+        };
+        let result = smart_graph_clone_derive(input).to_string();
+        assert_eq!(result, output.to_string(), "Weak pointer first: {}", result);
+    }
+
+    #[test]
+    fn test_graph_field_named_memo() {
+        let input = quote! {
+            enum Cache {
+                Filled { memo: String },
+            }
+        };
+        let memo_type = quote! {
+            ::std::collections::HashMap<usize, ::std::boxed::Box<dyn ::std::any::Any>>
+        };
+        // The `memo` parameter is hygienic: the field of the same name does not shadow it.
+        let output = quote! {
+            impl Cache {
+                fn clone_graph(&self) -> Self {
+                    self.clone_graph_in(&mut ::std::collections::HashMap::new())
+                }
+                #[doc(hidden)]
+                #[allow(unused_variables)]
+                fn clone_graph_in(&self, memo: &mut #memo_type) -> Self {
+                    match self {
+                        Cache::Filled { memo } => Cache::Filled {
+                            memo: {
+                                #[allow(dead_code)]
+                                trait GraphCloneFallback: Clone {
+                                    fn clone_graph_in(&self, _memo: &mut #memo_type) -> Self {
+                                        self.clone()
+                                    }
+                                }
+                                impl<T: Clone> GraphCloneFallback for T {}
+                                (memo).clone_graph_in(&mut *memo)
+                            }
+                        },
+                    }
+                }
+            }
+        };
+        let result = smart_graph_clone_derive(input).to_string();
+        assert_eq!(result, output.to_string(), "Field named memo: {}", result);
+    }
+
+    #[test]
+    fn test_into_generics() {
+        let input = quote! {
+            #[clone(into = EntryView)]
+            struct Entry<'a, T: Into<U>, U, const N: usize> where U: Default {
+                #[clone(exclude)]
+                key: &'a str,
+                value: T,
+                #[clone(exclude)]
+                history: [U; N],
+            }
+        };
+        // The lifetime, the const parameter and `U`, used by excluded fields only, are left out of
+        // the projection, along with the bounds mentioning them.
+        let output = quote! {
+            impl<'a, T: Into<U>, U, const N: usize> Clone for Entry<'a, T, U, N>
+            where
+                U: Default,
+                T: Clone,
+                U: Clone
+            {
+                fn clone(&self) -> Self {
+                    Self {
+                        key: ::core::clone::Clone::clone(&self.key),
+                        value: ::core::clone::Clone::clone(&self.value),
+                        history: ::core::clone::Clone::clone(&self.history),
+                    }
+                }
+                fn clone_from(&mut self, source: &Self) {
+                    self.key.clone_from(&source.key);
+                    self.value.clone_from(&source.value);
+                    self.history.clone_from(&source.history);
+                }
+            }
+            #[doc = "A projection of [`Entry`], without its excluded fields."]
+            struct EntryView<T> {
+                value: T,
+            }
+            impl<'a, T: Into<U>, U, const N: usize> Entry<'a, T, U, N>
+            where
+                U: Default,
+                T: Clone
+            {
+                #[doc(hidden)]
+                fn clone_into_projection(&self) -> EntryView<T> {
+                    EntryView {
+                        value: ::core::clone::Clone::clone(&self.value),
+                    }
+                }
+            }
+            impl<'a, T: Into<U>, U, const N: usize> ::core::convert::From<&Entry<'a, T, U, N> > for EntryView<T>
+            where
+                U: Default,
+                T: Clone
+            {
+                fn from(value: &Entry<'a, T, U, N>) -> Self {
+                    value.clone_into_projection()
+                }
+            }
+        };
+        let result = smart_clone_derive(input).to_string();
+        assert_eq!(
+            result,
+            output.to_string(),
+            "Projection generics: {}",
+            result
+        );
+    }
 }
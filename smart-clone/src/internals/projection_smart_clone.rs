@@ -0,0 +1,91 @@
+use proc_macro2::{Ident, TokenStream};
+use quote::quote;
+
+use crate::internals::ast::{Container, Data, Field, Style};
+use crate::internals::bound::{projection_generics, with_clone_bound};
+use crate::internals::{clone_data, Method};
+
+/// Generate the `#[clone(into = Name)]` projection of a struct or an enum: a `Name` type with the
+/// same fields and variants, except the excluded fields, along with `impl From<&Self> for Name`
+/// cloning each field according to its `#[clone...]` options.
+pub(crate) fn clone_projection(container: &Container, target: &Ident) -> TokenStream {
+    let name = container.ident;
+    let vis = container.vis;
+    let method = Method::Into(target);
+    let generics = with_clone_bound(container, method);
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+    let projection_generics = projection_generics(container);
+    let (definition_generics, projection_ty_generics, projection_where_clause) =
+        projection_generics.split_for_impl();
+
+    let definition = match &container.data {
+        Data::Struct(style, fields) => {
+            let fields = kept_fields(*style, fields, true);
+            match style {
+                Style::Named => quote! {
+                    #vis struct #target #definition_generics #projection_where_clause #fields
+                },
+                Style::Unnamed | Style::Unit => quote! {
+                    #vis struct #target #definition_generics #fields #projection_where_clause;
+                },
+            }
+        }
+        Data::Enum(variants) => {
+            let variants = variants.iter().map(|variant| {
+                let ident = variant.ident;
+                let fields = kept_fields(variant.style, &variant.fields, false);
+                quote! { #ident #fields }
+            });
+            quote! {
+                #vis enum #target #definition_generics #projection_where_clause {
+                    #(#variants,)*
+                }
+            }
+        }
+    };
+    let derives = &container.attrs.derive;
+    let derive = match derives.is_empty() {
+        true => None,
+        false => Some(quote! { #[derive(#(#derives),*)] }),
+    };
+    let doc = format!("A projection of [`{}`], without its excluded fields.", name);
+    let cloned = clone_data(container, method);
+
+    quote! {
+        #[doc = #doc]
+        #derive
+        #definition
+
+        impl #impl_generics #name #ty_generics #where_clause {
+            #[doc(hidden)]
+            fn clone_into_projection(&self) -> #target #projection_ty_generics {
+                #cloned
+            }
+        }
+
+        impl #impl_generics ::core::convert::From<&#name #ty_generics> for #target #projection_ty_generics #where_clause {
+            fn from(value: &#name #ty_generics) -> Self {
+                value.clone_into_projection()
+            }
+        }
+    }
+}
+
+/// The declaration of the fields of a struct or of an enum variant which are not excluded. The
+/// visibility of the fields is kept on structs only: the fields of a variant have none.
+fn kept_fields(style: Style, fields: &[Field], with_vis: bool) -> Option<TokenStream> {
+    let kept = fields.iter().filter(|field| field.attrs.exclude.is_none());
+    let vis = kept.clone().map(|field| match with_vis {
+        true => Some(field.vis),
+        false => None,
+    });
+    let types = kept.clone().map(|field| field.ty);
+    match style {
+        Style::Named => {
+            let members = kept.map(|field| &field.member);
+            Some(quote! { { #(#vis #members: #types,)* } })
+        }
+        Style::Unnamed => Some(quote! { ( #(#vis #types),* ) }),
+        Style::Unit => None,
+    }
+}
@@ -1,101 +1,145 @@
 use proc_macro2::{Ident, TokenStream};
 use quote::quote;
-use syn::{DataStruct, Field, Fields, LitInt, LitStr, Meta, Token};
-use syn::punctuated::Punctuated;
+use syn::{Index, Member};
 
-enum StructType {
-    Named,
-    Unnamed,
-    // Unit,
-}
+use crate::internals::ast::{Field, Style};
+use crate::internals::field_smart_clone::{
+    clone_field, is_cloned_last, never_cloned_field, reads_self,
+};
+use crate::internals::Method;
 
-/// Clone an enum.
-pub fn clone_struct_type(identity: &Ident, data_struct: DataStruct) -> TokenStream {
-    match &data_struct.fields {
-        Fields::Named(fields) => {
-            let cloned_fields = clone_fields(StructType::Named, &fields.named);
-            quote! { Self { #cloned_fields } }
+/// Clone a struct.
+pub fn clone_struct_type(
+    identity: &Ident,
+    style: Style,
+    fields: &[Field],
+    method: Method,
+) -> TokenStream {
+    // The projection of `#[clone(into = ...)]` is built without the excluded fields.
+    let (constructor, fields): (TokenStream, Vec<&Field>) = match method {
+        Method::Into(target) => (
+            quote! { #target },
+            fields
+                .iter()
+                .filter(|field| field.attrs.exclude.is_none())
+                .collect(),
+        ),
+        _ => (quote! { Self }, fields.iter().collect()),
+    };
+    // A field which must never be cloned fails the whole clone.
+    if let Some((field, _)) = never_cloned_field(fields.iter().copied(), method) {
+        let member = &field.member;
+        return clone_field(field, method, quote! { &self.#member }, quote! { self }).value;
+    }
+    match style {
+        Style::Named if fields.iter().any(|field| !field.attrs.uses.is_empty()) => {
+            clone_fields_in_order(constructor, &fields, method)
+        }
+        Style::Named => {
+            let cloned_fields = clone_fields(&fields, method);
+            quote! { #constructor { #cloned_fields } }
         }
-        Fields::Unnamed(fields) => {
-            let cloned_fields = clone_fields(StructType::Unnamed, &fields.unnamed);
-            quote! { #identity { #cloned_fields } }
+        Style::Unnamed => {
+            let constructor = match method {
+                Method::Into(_) => constructor,
+                _ => quote! { #identity },
+            };
+            let cloned_fields = clone_fields(&fields, method);
+            quote! { #constructor { #cloned_fields } }
         }
-        Fields::Unit => quote! { Self { } },
+        Style::Unit => quote! { #constructor { } },
     }
 }
 
-/// Convert fields according to there type.
-fn clone_fields(struct_type: StructType, fields: &Punctuated<Field, Token![,]>) -> TokenStream {
-    let clone_fields = fields.iter().enumerate().map(|(i, field)| {
-        let field_name = &field.ident;
-        let field_id = LitInt::new(&format!("{}", i), proc_macro2::Span::call_site());
-
-        // Check for the `#[clone...]` attribute
-        match &field
-            .attrs
-            .iter()
-            .find(|attr| attr.path().is_ident("clone"))
-        {
-            // Field is not marked: clone it as usual.
-            None => match struct_type {
-                StructType::Named => quote! { #field_name: self.#field_name.clone() },
-                StructType::Unnamed => quote! { #field_id: self.#field_id.clone() },
-            },
-            // Field is marked: smart clone it!
-            Some(attr) => match &attr.meta {
-                // Handle `#[clone]` by cloning as usual
-                Meta::Path(_) => match struct_type {
-                    StructType::Named => quote! { #field_name: self.#field_name.clone() },
-                    StructType::Unnamed => quote! { #field_id: self.#field_id.clone() },
-                },
-                // Handle #[clone = value].
-                Meta::NameValue(item) => {
-                    let value = &item.value;
-                    match struct_type {
-                        StructType::Named => quote! { #field_name: #value },
-                        StructType::Unnamed => quote! { #field_id: #value },
-                    }
-                }
-                // Handle `#[clone(item1, item2)]` as `#[clone(items)]`.
-                Meta::List(items) => {
-                    let tokens = items.tokens.clone();
+/// Clone a struct into an existing value: fields cloned as usual reuse their resources with
+/// `clone_from`, the others are assigned.
+///
+/// Returns `None` to keep the default `clone_from` (ie: `*self = source.clone()`) when there is
+/// nothing to reuse, when an override reads `self`, which would be the destination here, when
+/// an override reads the cloned value of another field with `uses(...)`, or when a field must never
+/// be cloned.
+pub fn clone_from_struct_type(fields: &[Field]) -> Option<TokenStream> {
+    if never_cloned_field(fields, Method::Clone).is_some() {
+        return None;
+    }
+    let mut assignments = Vec::new();
+    for field in fields {
+        if !field.attrs.uses.is_empty() {
+            return None;
+        }
+        let member = &field.member;
+        if field.attrs.is_standard() {
+            assignments.push(quote! { self.#member.clone_from(&source.#member); });
+            continue;
+        }
+        let cloned = clone_field(
+            field,
+            Method::Clone,
+            quote! { &source.#member },
+            quote! { source },
+        );
+        if reads_self(field) {
+            return None;
+        }
+        let value = cloned.value;
+        assignments.push(quote! { self.#member = #value; });
+    }
 
-                    // Case #[clone(...)]
-                    let mut clone_value: Option<TokenStream> = None;
-                    let _ = items.parse_nested_meta(|meta| {
-                        // `#[clone(default)]` => clone with default value
-                        if meta.path.is_ident("default") {
-                            clone_value = Some(quote! {
-                                Default::default()
-                            });
-                        }
-                        // `#[clone(clone_with =func)]`
-                        if meta.path.is_ident("clone_with") && meta.input.peek(Token![=]) {
-                            let func: LitStr = meta.value()?.parse()?;
-                            let func: TokenStream = func.parse()?;
-                            clone_value = match struct_type {
-                                StructType::Named => Some(quote! {
-                                    #func(&self.#field_name)
-                                }),
-                                StructType::Unnamed => Some(quote! {
-                                    #func(&self.#field_id)
-                                }),
-                            };
-                        }
-                        Ok(())
-                    });
+    match assignments.is_empty() {
+        true => None,
+        false => Some(quote! {
+            fn clone_from(&mut self, source: &Self) {
+                #(#assignments)*
+            }
+        }),
+    }
+}
 
-                    let value = clone_value.unwrap_or(tokens);
-                    match struct_type {
-                        StructType::Named => quote! { #field_name: #value },
-                        StructType::Unnamed => quote! { #field_id: #value },
-                    }
-                }
-            },
-        }
+/// Convert fields according to there type.
+///
+/// Unnamed fields are set by position, which differs from their index in a projection without
+/// the excluded fields. The fields holding a `Weak` pointer are cloned last by `clone_graph`.
+fn clone_fields(fields: &[&Field], method: Method) -> TokenStream {
+    let mut ordered: Vec<_> = fields.iter().enumerate().collect();
+    ordered.sort_by_key(|(_, field)| is_cloned_last(field, method));
+    let clone_fields = ordered.into_iter().map(|(position, field)| {
+        let member = &field.member;
+        let cloned = clone_field(field, method, quote! { &self.#member }, quote! { self });
+        let value = cloned.value;
+        let target = match member {
+            Member::Named(_) => member.clone(),
+            Member::Unnamed(_) => Member::Unnamed(Index::from(position)),
+        };
+        quote! { #target: #value }
     });
 
     quote! {
         #(#clone_fields,)*
     }
 }
+
+/// Clone named fields one after the other in declaration order, binding each cloned value to the
+/// name of its field so that the overrides listing it in `uses(...)` can read it.
+fn clone_fields_in_order(
+    constructor: TokenStream,
+    fields: &[&Field],
+    method: Method,
+) -> TokenStream {
+    let (bindings, members): (Vec<_>, Vec<_>) = fields
+        .iter()
+        .map(|field| {
+            let member = &field.member;
+            let cloned = clone_field(field, method, quote! { &self.#member }, quote! { self });
+            let ty = field.ty;
+            let value = cloned.value;
+            (quote! { let #member: #ty = #value; }, member)
+        })
+        .unzip();
+
+    quote! {
+        {
+            #(#bindings)*
+            #constructor { #(#members),* }
+        }
+    }
+}
@@ -4,7 +4,9 @@ extern crate proc_macro;
 
 use proc_macro::TokenStream;
 
-use crate::internals::smart_clone_derive;
+use crate::internals::{
+    smart_clone_derive, smart_deep_clone_derive, smart_graph_clone_derive, smart_try_clone_derive,
+};
 
 mod internals;
 
@@ -24,9 +26,77 @@ mod internals;
 ///
 /// - `#[clone]`: will perform cloning as usual for your field. Equivalent to no annotation.
 /// - `#[clone = xxx]`: will set the value `xxx` to the field when the structure is cloned
-/// - `#[clone(xxx)]`: same as above, but `xxx` can be whatever you want here, not just a literal
-/// - `#[clone(clone_with = "xxx")]`: the field will be passed by reference to a function called `xxx` and the
-/// returned value will be used when the structure is cloned.
+/// - `#[clone(value = xxx)]`: same as above, but `xxx` can be whatever you want here, not just a literal
+/// - `#[clone(default)]`: the field will be reset to `Default::default()` when the structure is cloned
+/// - `#[clone(with = xxx)]`: the field will be passed by reference to the function `xxx` (a path like
+///   `Self::reset` or `reset::<u32>`, or a closure) and the returned value will be used when the structure is cloned.
+/// - `#[clone(clone_with = "xxx")]`: former syntax of `with`, with the function path given as a string.
+/// - `#[clone(with_parent = xxx)]`: same as `with`, but the function is called as `xxx(&self.field, self)`.
+/// - `#[clone(uses(a, b))]`: the override reads the cloned values of the fields `a` and `b`, declared
+///   before it, which are bound by name.
+/// - `#[clone(set(a.b = xxx, ...))]`: the field is cloned as usual, then the nested places are
+///   assigned.
+/// - `#[clone(map = xxx)]`: the cloned value is passed to `xxx`, which returns the value to use,
+///   after the other options: `#[clone(deep, map = xxx)]` maps the deep clone.
+/// - `#[clone(deep)]`: the `Rc`, `Arc` and `Box` pointers of the field are allocated anew around a
+///   clone of their pointee, through `Option`, `Vec`, `RefCell`, `Mutex` and `RwLock` layers.
+/// - `#[clone(with_ctx = xxx)]`: in `clone_with_ctx`, the field is computed by `xxx(&self.field, ctx)`.
+/// - `#[clone(to = Variant)]`: on an enum variant, the value is remapped to `Variant`: the fields of
+///   the same name (or position) are moved across, the other fields of `Variant` are defaulted.
+/// - `#[clone(with_fields = xxx)]`: on an enum variant, the value is computed by `xxx` from references
+///   to the fields of the variant, in declaration order: `xxx(x, y)`.
+/// - `#[clone(reset)]`: on an enum variant, the variant is kept and each of its fields is reset to its
+///   override, or to `Default::default()`. Unlike `default`, the enum itself does not need `Default`.
+/// - `#[clone(panic = "reason")]`: the field or the variant must never be cloned: cloning it panics
+///   with a message naming the type, the variant and the field.
+/// - `#[clone(unreachable)]`: same as `panic`, with `unreachable!`.
+/// - `#[clone(forbid)]`: same as `panic`, but `try_clone` returns an error instead.
+///
+/// Options given in several `#[clone(...)]` attributes are merged, conflicting ones are rejected.
+///
+/// The former `#[clone(xxx)]` syntax, without `value =`, still works but is deprecated, with a
+/// warning, in favor of `#[clone(value = xxx)]`.
+///
+/// These options can be used on struct fields, on enum variants, and on the fields of an enum
+/// variant.
+///
+/// Overrides can read the original value through `self`: `#[clone(value = self.generation + 1)]`.
+/// The `value` of an enum variant can also read its fields, bound by name, or as `v0, v1...` for
+/// unnamed fields.
+/// When a field `uses(...)` other ones, the fields are cloned one after the other in declaration
+/// order.
+///
+/// A list starting with `profile = "name"`, like `#[clone(profile = "template", default)]`, sets the
+/// strategy of a field (or variant) for this profile only: an inherent `clone_<name>(&self)` is
+/// generated for each profile, where the fields with no setting for it keep their base strategy.
+/// The names of the other generated methods, like `from` or `graph`, cannot be used.
+///
+/// `#[clone(default_fields = "cache_*, *_scratch")]` on the struct or enum resets the fields whose
+/// name matches a pattern, and `#[clone(all = default, except(id))]` resets all the fields but the
+/// listed ones (`all = deep` clones them deeply). `#[clone(for_type(Instant = "Instant::now()"))]`
+/// sets the fields of a type, taking priority over them (`for_type(Type = default)` and
+/// `for_type(Type = deep)` are accepted as well). Fields with a strategy of their own, a bare
+/// `#[clone]` included, keep it.
+///
+/// `#[clone(context = Ctx)]` on the struct or enum generates an inherent
+/// `clone_with_ctx(&self, ctx: &mut Ctx) -> Self`, in which `with_ctx` fields are computed from the
+/// context. `Clone` is still implemented, cloning them as usual, unless `skip_clone` is given.
+///
+/// `#[clone(builder)]` on a struct with named fields generates a `<Name>CloneBuilder`, returned by
+/// `clone_builder(&self)`: its setters replace fields, and `build()` clones the other ones only.
+///
+/// `#[clone(mask)]` on a struct with named fields generates `clone_except(&self, mask)` and
+/// `clone_only(&self, fields)`, taking a runtime set of `<Name>Field`: masked fields are reset to
+/// their override, or to `Default::default()`. Fields marked with `#[clone(keep)]` are never reset.
+///
+/// `#[clone(into = Name, derive(...))]` generates a `Name` struct or enum without the fields marked
+/// with `#[clone(exclude)]`, along with `impl From<&Self> for Name`.
+///
+/// An optimized `clone_from` is generated as well: fields cloned as usual reuse their resources.
+///
+/// Generic types are supported: a `T: Clone` bound is only added for the type parameters used by
+/// fields that are cloned as usual. `#[clone(bound = "...")]` replaces the inferred bounds, either on
+/// the struct/enum (for the whole type) or on a field (for this field only).
 ///
 /// # Examples
 ///
@@ -36,15 +106,15 @@ mod internals;
 /// # fn main() {
 ///   #[derive(SmartClone)]
 ///   struct Foo {
-///       #[clone(12)]
+///       #[clone(value = 12)]
 ///       a: i32, // will always be cloned to value 12
-///       #[clone("banana".to_owned())]
+///       #[clone(value = "banana".to_owned())]
 ///       b: String, // this field will always clone to String `banana`
 ///       #[clone(default)]
 ///       c: Option<i32>, // this field will always be reset to default when Foo is cloned
 ///       #[clone(clone_with = "double")]
 ///       d: Vec<u32>, // uses a custom method to clone this field
-///       #[clone("banana".to_owned())]
+///       #[clone(value = "banana".to_owned())]
 ///       e: String,
 ///   }
 ///
@@ -54,14 +124,15 @@ mod internals;
 ///       A,
 ///       B(usize, usize), // will behave as usual
 ///       C { x: u8, y: u8 },
-///       #[clone(SimpleEnum::D(8, 12))]
+///       #[clone(value = SimpleEnum::D(8, 12))]
 ///       D(i32, u32),
-///       #[clone(SimpleEnum::E { x: 3, y: 4 })]
+///       #[clone(value = SimpleEnum::E { x: 3, y: 4 })]
 ///       E { x: u8, y: u8 },
-///       #[clone(clone_with = "double")]
-///       F { x: u8, y: u8 },
+///       #[clone(with_fields = double)]
+///       F { x: u8, y: u8 }, // computed as `double(x, y)`
 ///       #[clone(default)]
 ///       G { x: u8, y: u8 },
+///       H { x: u8, #[clone(default)] cache: Vec<u8> }, // only `cache` is reset
 ///   }
 /// # }
 /// ```
@@ -69,3 +140,118 @@ mod internals;
 pub fn smart_clone_derive_macro(input: TokenStream) -> TokenStream {
     smart_clone_derive(input.into()).into()
 }
+
+/// # Smart Try Clone
+///
+/// `SmartTryClone` is a derive macro that generates an inherent
+/// `fn try_clone(&self) -> Result<Self, E>`, for types holding fields which can only be
+/// duplicated fallibly (`File::try_clone`, `TcpStream::try_clone`, FFI handles...).
+///
+/// It reads the same `#[clone(...)]` attributes as `SmartClone`, plus:
+///
+/// - `#[clone(try_with = xxx)]` on a field or a variant: the value is computed by the fallible
+///   function `xxx`, whose error is propagated with `?`.
+/// - `#[clone(error = MyError)]` on the struct or enum: the error type `E`, which the errors of
+///   the `try_with` functions are converted into. Defaults to
+///   `Box<dyn Error + Send + Sync>`, with a message naming the failing field.
+/// - `#[clone(forbid)]` on a field or a variant: `try_clone` returns an error naming it, converted
+///   from a `String` into `E`.
+///
+/// # Examples
+///
+/// ```
+/// use std::fs::File;
+/// use smart_clone::SmartTryClone;
+///
+/// #[derive(SmartTryClone)]
+/// #[clone(error = std::io::Error)]
+/// struct Log {
+///     name: String,
+///     #[clone(try_with = File::try_clone)]
+///     file: File,
+/// }
+/// ```
+#[proc_macro_derive(SmartTryClone, attributes(clone))]
+pub fn smart_try_clone_derive_macro(input: TokenStream) -> TokenStream {
+    smart_try_clone_derive(input.into()).into()
+}
+
+/// # Deep Clone
+///
+/// `DeepClone` is a derive macro that generates an inherent `fn deep_clone(&self) -> Self`, which
+/// unshares a whole object tree in one call: every field is cloned as with `#[clone(deep)]`.
+///
+/// - The `Rc`, `Arc` and `Box` pointers are allocated anew around a clone of their pointee,
+///   through `Option`, `Vec`, `RefCell`, `Mutex` and `RwLock` layers. The pointers to an unsized
+///   value (`Arc<str>`, `Box<[T]>`, `Rc<dyn Trait>`) are cloned as usual.
+/// - The values which derive `DeepClone` as well are cloned with their own `deep_clone`, the
+///   others with `Clone::clone`.
+///
+/// It reads the same `#[clone(...)]` attributes as `SmartClone`: fields with another strategy
+/// (`default`, `value = ...`, `with = ...`) keep it.
+///
+/// # Limitations
+///
+/// There is no `DeepClone` trait, as a proc-macro crate cannot export one: `deep_clone` is an
+/// inherent method of the concrete types deriving `DeepClone`. A `T: DeepClone` bound cannot be
+/// written, and the values reached through a generic parameter are always cloned with
+/// `Clone::clone`, even when `T` derives `DeepClone`.
+///
+/// # Examples
+///
+/// ```
+/// use std::sync::{Arc, RwLock};
+/// use smart_clone::{DeepClone, SmartClone};
+///
+/// #[derive(SmartClone, DeepClone)]
+/// struct Config {
+///     values: Arc<RwLock<Vec<u32>>>,
+/// }
+///
+/// #[derive(SmartClone, DeepClone)]
+/// struct Document {
+///     name: String,
+///     config: Arc<Config>, // deep_clone allocates a new `Config`, with its own values
+/// }
+/// ```
+#[proc_macro_derive(DeepClone, attributes(clone))]
+pub fn smart_deep_clone_derive_macro(input: TokenStream) -> TokenStream {
+    smart_deep_clone_derive(input.into()).into()
+}
+
+/// # Graph Clone
+///
+/// `GraphClone` is a derive macro that generates an inherent `fn clone_graph(&self) -> Self`, which
+/// duplicates a graph of shared `Rc`/`Arc` nodes while preserving its shape: where
+/// [`DeepClone`](derive.DeepClone.html) would turn a DAG into a tree, `clone_graph` clones each
+/// shared node exactly once and shares the new node in the copy as in the original.
+///
+/// - The nodes are identified by their address in a memo table, so they must be `'static`.
+/// - The `Weak` pointers are re-pointed at the new nodes, as long as their target is cloned before
+///   them (like a parent link, whose target is cloned while its children are). The fields holding
+///   a `Weak` pointer are cloned after the other fields of their struct or variant, whatever their
+///   order. The `Weak` pointers whose target is not part of the cloned value keep pointing at the
+///   original node.
+/// - The values which derive `GraphClone` as well are cloned with the same memo table, the others
+///   with `Clone::clone`.
+///
+/// A strong reference cycle cannot be cloned and panics.
+///
+/// # Examples
+///
+/// ```
+/// use std::cell::RefCell;
+/// use std::rc::{Rc, Weak};
+/// use smart_clone::GraphClone;
+///
+/// #[derive(GraphClone)]
+/// struct Node {
+///     name: String,
+///     parent: RefCell<Weak<Node>>,
+///     children: RefCell<Vec<Rc<Node>>>,
+/// }
+/// ```
+#[proc_macro_derive(GraphClone, attributes(clone))]
+pub fn smart_graph_clone_derive_macro(input: TokenStream) -> TokenStream {
+    smart_graph_clone_derive(input.into()).into()
+}